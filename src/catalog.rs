@@ -0,0 +1,337 @@
+use crate::{sha256_file, ProviderRegistry, RidoError, WindowsData, WindowsEntry, WindowsVersion};
+use serde::{Deserialize, Serialize};
+use std::{fmt, time::SystemTime};
+
+/// A snapshot of every resolvable entry, which can be saved as JSON and compared against later snapshots
+/// to find out when Microsoft refreshes its media.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Catalog {
+    pub format_version: u32,
+    /// Unix timestamp at which the snapshot was taken
+    pub created: u64,
+    pub images: Vec<CatalogImage>,
+    /// Entries which could not be resolved while taking the snapshot
+    #[serde(default)]
+    pub failures: Vec<CatalogFailure>,
+}
+
+/// Identifies an image by its release, language and architecture names, as displayed to users
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct CatalogKey {
+    pub release: String,
+    pub language: String,
+    pub arch: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatalogImage {
+    #[serde(flatten)]
+    pub key: CatalogKey,
+    pub url: String,
+    pub hash: Option<String>,
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub version: WindowsVersion,
+    /// Unix timestamp at which the entry was resolved
+    pub resolved: u64,
+    /// Reported by the server when the catalog is taken with `resolve_remote`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatalogFailure {
+    #[serde(flatten)]
+    pub key: CatalogKey,
+    pub error: String,
+}
+
+impl Catalog {
+    pub const FORMAT_VERSION: u32 = 1;
+
+    /// Resolve every entry offered by the registry. If `resolve_remote` is set, redirects are followed
+    /// to find sizes and builds, at the cost of an extra request per entry.
+    pub fn snapshot(registry: &ProviderRegistry, resolve_remote: bool) -> Self {
+        Self::snapshot_entries(registry, registry.list_all(), resolve_remote)
+    }
+
+    /// Resolve only the given entries
+    pub fn snapshot_entries(registry: &ProviderRegistry, entries: impl IntoIterator<Item = WindowsEntry>, resolve_remote: bool) -> Self {
+        let mut images = Vec::new();
+        let mut failures = Vec::new();
+        for entry in entries {
+            let key = CatalogKey::from(&entry);
+            let data = registry.resolve(entry).and_then(|mut data| {
+                if resolve_remote {
+                    data.resolve_remote()?;
+                }
+                Ok(data)
+            });
+            match data {
+                Ok(data) => images.push(CatalogImage::from(data)),
+                Err(e) => failures.push(CatalogFailure { key, error: e.to_string() }),
+            }
+        }
+        Self {
+            format_version: Self::FORMAT_VERSION,
+            created: unix_time(),
+            images,
+            failures,
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, RidoError> {
+        serde_json::from_str(json).map_err(RidoError::JSONParsing)
+    }
+    pub fn to_json(&self) -> Result<String, RidoError> {
+        serde_json::to_string_pretty(self).map_err(RidoError::JSONParsing)
+    }
+
+    pub fn get(&self, key: &CatalogKey) -> Option<&CatalogImage> {
+        self.images.iter().find(|image| image.key == *key)
+    }
+
+    /// Every image with the given SHA-256 hash
+    pub fn find_hash(&self, hash: &str) -> impl Iterator<Item = &CatalogImage> {
+        let hash = hash.to_ascii_lowercase();
+        self.images
+            .iter()
+            .filter(move |image| image.hash.as_ref().is_some_and(|h| h.to_ascii_lowercase() == hash))
+    }
+
+    /// Hash a local file, and find the entries of the images it matches
+    pub fn identify_file(&self, path: impl AsRef<std::path::Path>) -> Result<Vec<WindowsEntry>, RidoError> {
+        let hash = sha256_file(path.as_ref())?;
+        self.find_hash(&hash).map(|image| image.key.entry()).collect()
+    }
+
+    /// Compare this (older) snapshot against a newer one
+    pub fn diff(&self, newer: &Self) -> CatalogDiff {
+        let mut diff = CatalogDiff::default();
+        for image in &newer.images {
+            let Some(old) = self.get(&image.key) else {
+                diff.added.push(image.key.clone());
+                continue;
+            };
+            if old.version != image.version && image.version != WindowsVersion::default() {
+                diff.new_builds.push(CatalogChange {
+                    key: image.key.clone(),
+                    old: old.version.to_string(),
+                    new: image.version.to_string(),
+                });
+            }
+            if let (Some(old_hash), Some(new_hash)) = (&old.hash, &image.hash) {
+                if !old_hash.eq_ignore_ascii_case(new_hash) {
+                    diff.changed_hashes.push(CatalogChange {
+                        key: image.key.clone(),
+                        old: old_hash.clone(),
+                        new: new_hash.clone(),
+                    });
+                }
+            } else if let Some((old, new)) = old.changed_file(image) {
+                // Microsoft doesn't publish hashes for most entries, but the server's metadata still changes along with the file
+                diff.changed_files.push(CatalogChange { key: image.key.clone(), old, new });
+            }
+        }
+        // An entry which merely failed to resolve this time hasn't been removed
+        diff.removed = self
+            .images
+            .iter()
+            .map(|image| &image.key)
+            .filter(|key| newer.get(key).is_none() && !newer.failures.iter().any(|failure| failure.key == **key))
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CatalogDiff {
+    pub added: Vec<CatalogKey>,
+    pub removed: Vec<CatalogKey>,
+    pub new_builds: Vec<CatalogChange>,
+    pub changed_hashes: Vec<CatalogChange>,
+    /// Images without a hash whose ETag, Last-Modified date or size changed
+    #[serde(default)]
+    pub changed_files: Vec<CatalogChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatalogChange {
+    #[serde(flatten)]
+    pub key: CatalogKey,
+    pub old: String,
+    pub new: String,
+}
+
+impl CatalogDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.new_builds.is_empty() && self.changed_hashes.is_empty() && self.changed_files.is_empty()
+    }
+}
+
+impl fmt::Display for CatalogKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}, {})", self.release, self.language, self.arch)
+    }
+}
+
+impl fmt::Display for CatalogDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for key in &self.added {
+            writeln!(f, "Added: {key}")?;
+        }
+        for key in &self.removed {
+            writeln!(f, "Removed: {key}")?;
+        }
+        for change in &self.new_builds {
+            writeln!(f, "New build: {} {} -> {}", change.key, change.old, change.new)?;
+        }
+        for change in &self.changed_hashes {
+            writeln!(f, "Changed hash: {} {} -> {}", change.key, change.old, change.new)?;
+        }
+        for change in &self.changed_files {
+            writeln!(f, "Changed file: {} {} -> {}", change.key, change.old, change.new)?;
+        }
+        Ok(())
+    }
+}
+
+impl CatalogKey {
+    /// The entry this key was created from
+    pub fn entry(&self) -> Result<WindowsEntry, RidoError> {
+        WindowsEntry::new(self.release.as_str(), self.language.as_str(), self.arch.as_str())
+    }
+}
+
+impl From<&WindowsEntry> for CatalogKey {
+    fn from(entry: &WindowsEntry) -> Self {
+        Self {
+            release: entry.release.to_string(),
+            language: entry.lang.to_string(),
+            arch: entry.arch.to_string(),
+        }
+    }
+}
+
+impl CatalogImage {
+    /// The first piece of server metadata known in both snapshots which differs, strongest first
+    fn changed_file(&self, newer: &Self) -> Option<(String, String)> {
+        let size = |image: &Self| image.size.map(|size| size.to_string());
+        [
+            (self.etag.clone(), newer.etag.clone()),
+            (self.last_modified.clone(), newer.last_modified.clone()),
+            (size(self), size(newer)),
+        ]
+        .into_iter()
+        .find_map(|pair| match pair {
+            (Some(old), Some(new)) => Some((old, new)),
+            _ => None,
+        })
+        .filter(|(old, new)| old != new)
+    }
+}
+
+impl From<WindowsData> for CatalogImage {
+    fn from(data: WindowsData) -> Self {
+        let remote = data.remote.as_ref();
+        Self {
+            key: CatalogKey::from(&data.info),
+            url: remote.map_or(&data.url, |remote| &remote.final_url).clone(),
+            hash: data.hash,
+            size: data.size.or(remote.and_then(|remote| remote.content_length)),
+            version: data.version,
+            resolved: unix_time(),
+            etag: remote.and_then(|remote| remote.etag.clone()),
+            last_modified: remote.and_then(|remote| remote.last_modified.clone()),
+        }
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("System clock is broken")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(hash: Option<&str>, etag: Option<&str>, size: Option<u64>) -> CatalogImage {
+        CatalogImage {
+            key: CatalogKey {
+                release: "Windows 11".to_string(),
+                language: "English (United States)".to_string(),
+                arch: "x86_64".to_string(),
+            },
+            url: "https://software.download.prss.microsoft.com/Win11_24H2_English_x64.iso".to_string(),
+            hash: hash.map(str::to_string),
+            size,
+            version: WindowsVersion::default(),
+            resolved: 0,
+            etag: etag.map(str::to_string),
+            last_modified: None,
+        }
+    }
+
+    fn catalog(image: CatalogImage) -> Catalog {
+        Catalog {
+            format_version: Catalog::FORMAT_VERSION,
+            created: 0,
+            images: vec![image],
+            failures: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn unhashed_images_change_with_their_etag() {
+        let old = catalog(image(None, Some("\"0x8DCD1\""), Some(5819484160)));
+        let new = catalog(image(None, Some("\"0x8DD3A\""), Some(5819484160)));
+        let diff = old.diff(&new);
+        assert!(diff.changed_hashes.is_empty());
+        assert_eq!(diff.changed_files.len(), 1);
+        assert_eq!(
+            (diff.changed_files[0].old.as_str(), diff.changed_files[0].new.as_str()),
+            ("\"0x8DCD1\"", "\"0x8DD3A\"")
+        );
+        assert!(old.diff(&old).is_empty());
+    }
+
+    #[test]
+    fn size_is_compared_without_an_etag() {
+        let old = catalog(image(None, None, Some(5819484160)));
+        let new = catalog(image(None, Some("\"0x8DD3A\""), Some(5836851200)));
+        assert_eq!(old.diff(&new).changed_files[0].new, "5836851200");
+    }
+
+    #[test]
+    fn keys_parse_back_into_entries() {
+        for entry in WindowsEntry::list_all() {
+            assert_eq!(CatalogKey::from(&entry).entry().unwrap(), entry);
+        }
+    }
+
+    #[test]
+    fn identify_file() {
+        let path = std::env::temp_dir().join(format!("rido-catalog-identify-{}.iso", std::process::id()));
+        std::fs::write(&path, b"not really an iso").unwrap();
+        let entry = WindowsEntry::list_all().remove(0);
+        let mut image = image(Some(&sha256_file(&path).unwrap().to_ascii_uppercase()), None, None);
+        image.key = CatalogKey::from(&entry);
+        assert_eq!(catalog(image).identify_file(&path).unwrap(), [entry]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn hashes_take_precedence() {
+        let old = catalog(image(Some("aa"), Some("\"0x8DCD1\""), None));
+        let new = catalog(image(Some("bb"), Some("\"0x8DD3A\""), None));
+        let diff = old.diff(&new);
+        assert_eq!(diff.changed_hashes.len(), 1);
+        assert!(diff.changed_files.is_empty());
+    }
+}
@@ -0,0 +1,12 @@
+use sha2::{Digest, Sha256};
+use std::{fmt::Write, fs::File, io, path::Path};
+
+/// Stream a file through SHA-256, returning the lowercase hex digest
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().iter().fold(String::with_capacity(64), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    }))
+}
@@ -1,96 +1,227 @@
-use crate::{RidoError, ValidateLanguage, ValidateWithArch, WindowsArchitecture, WindowsLanguage, WindowsRelease};
+use crate::{Provider, ProviderCapabilities, ResolvedImage, RidoError, ValidateLanguage, ValidateWithArch, WindowsArchitecture, WindowsEntry, WindowsLanguage, WindowsRelease, WindowsVersion};
 use reqwest::header::{ACCEPT, REFERER, USER_AGENT};
+use scraper::{Html, Selector};
 use serde::Deserialize;
 use std::{fmt, time::SystemTime};
+use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 use uuid::Uuid;
 
 const FIREFOX_124_RELEASE_TIME: u64 = 1710806400;
 const FOUR_WEEKS: u64 = 2419200;
 
-pub fn get_consumer_info(release: ConsumerRelease, lang: ConsumerLanguage, arch: WindowsArchitecture) -> Result<(String, Option<String>), RidoError> {
-    if arch == WindowsArchitecture::i686 && release == ConsumerRelease::Eleven {
-        return Err(RidoError::InvalidArchitecture(release.into(), arch));
+pub struct ConsumerProvider;
+
+impl Provider for ConsumerProvider {
+    fn name(&self) -> &str {
+        "consumer"
+    }
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            hashes: false,
+            versions: true,
+            redirects: false,
+            network: true,
+        }
+    }
+    fn entries(&self) -> Vec<WindowsEntry> {
+        ConsumerRelease::iter()
+            .flat_map(|rel| {
+                let release: WindowsRelease = rel.into();
+                ConsumerLanguage::iter()
+                    .filter(move |&lang| lang.validate(release))
+                    .flat_map(move |lang| {
+                        let lang = lang.into();
+                        WindowsArchitecture::iter()
+                            .filter(move |&arch| rel.validate(arch))
+                            .map(move |arch| WindowsEntry { release, lang, arch })
+                    })
+            })
+            .collect()
+    }
+    fn supports(&self, entry: &WindowsEntry) -> bool {
+        matches!(entry.release, WindowsRelease::Consumer(_))
     }
+    fn resolve(&self, entry: &WindowsEntry) -> Result<ResolvedImage, RidoError> {
+        match (entry.release, entry.lang) {
+            (WindowsRelease::Consumer(release), WindowsLanguage::Consumer(lang)) => get_consumer_info(release, lang, entry.arch),
+            #[allow(unreachable_patterns)]
+            _ => Err(RidoError::InvalidSelection),
+        }
+    }
+}
 
-    let url = match release {
-        ConsumerRelease::Ten => "https://microsoft.com/en-us/software-download/windows10ISO",
-        ConsumerRelease::Eleven => "https://microsoft.com/en-us/software-download/windows11",
-        _ => "",
-    };
+pub fn get_consumer_info(release: ConsumerRelease, lang: ConsumerLanguage, arch: WindowsArchitecture) -> Result<ResolvedImage, RidoError> {
     let isotype = match arch {
         WindowsArchitecture::x86_64 => "x64",
         WindowsArchitecture::i686 => "x32",
     };
+    let session = Session::new()?;
 
-    let user_agent = {
-        // Choose latest firefox release based on Firefox's 4 week release schedule
-        let unix_time = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("System clock is broken");
-        let firefox_release = 124 + (unix_time.as_secs() - FIREFOX_124_RELEASE_TIME) / FOUR_WEEKS;
-        format!("Mozilla 5.0 (X11, Linux x86_64; rv:{firefox_release}.0) Gecko/20100101 Firefox/{firefox_release}.0")
-    };
-
-    let uuid = Uuid::new_v4().to_string();
-
-    let client = reqwest::blocking::Client::new();
-
-    let product_id = if let ConsumerRelease::CustomProductID(id) = release {
-        id.to_string()
+    let (referer, skus) = if let ConsumerRelease::CustomProductID(id) = release {
+        let (info, skus) = session.product_info(id)?;
+        if !info.languages.contains(&lang) {
+            return Err(RidoError::InvalidLanguage {
+                release: release.into(),
+                language: lang.to_string(),
+                allowed: info.languages.iter().map(ToString::to_string).collect(),
+            });
+        }
+        // The product can't be any more available than the release it belongs to
+        if !info.family.validate(arch) {
+            return Err(RidoError::InvalidArchitecture {
+                release: release.into(),
+                arch,
+                allowed: WindowsArchitecture::iter().filter(|&arch| info.family.validate(arch)).collect(),
+            });
+        }
+        let referer = info
+            .family
+            .download_page()
+            .ok_or(RidoError::UnknownProduct(id, info.display_name))?;
+        (referer, skus)
+    } else if let Some(product_id) = release.product_id() {
+        // Older releases aren't on their download page's edition picker any more, but are still served by ID
+        let referer = release.download_page().ok_or(RidoError::ProductID)?;
+        (referer, session.skus(product_id)?)
     } else {
-        let download_page_html = client
+        let url = release.download_page().ok_or(RidoError::ProductID)?;
+        let download_page_html = session
+            .client
             .get(url)
-            .header(USER_AGENT, &user_agent)
+            .header(USER_AGENT, &session.user_agent)
             .header(ACCEPT, "")
             .send()?
             .text()?;
-
-        download_page_html[..std::cmp::min(download_page_html.len(), 102400)]
-            .split("option")
-            .find_map(|value| {
-                let start = value.find("value=\"")? + 7;
-                let end = value.find("\">Windows")?;
-                Some(value.get(start..end).unwrap())
-            })
-            .ok_or(RidoError::ProductID)?
-            .to_string()
+        let product_id = parse_product_id(&download_page_html).ok_or(RidoError::ProductID)?;
+        (url, session.skus(product_id)?)
     };
 
-    client
-        .get(format!("https://vlscppe.microsoft.com/tags?org_id=y6jn8c31&session_id={uuid}",))
-        .header(ACCEPT, "")
-        .header(USER_AGENT, &user_agent)
-        .send()?;
-
-    let skuid_table = get_skus(&client, &product_id, &uuid)?;
-    let sku = skuid_table
+    let sku = skus
         .into_iter()
-        .find(|s| s.localized_language == lang.to_string())
+        .find(|s| lang.matches_sku(&s.localized_language))
         .ok_or(RidoError::SKUID)?;
     let skuid = sku.id;
 
-    let referer = if let ConsumerRelease::CustomProductID(_) = release {
-        // Product names are formatted as 'Windows <release> <release tag>'. e.g. 'Windows 11 24H2'
-        // We can just grab the second space-delimited value and it should be release
-        let release = sku.product_display_name.split_ascii_whitespace().nth(1).unwrap_or("11");
-        &format!("https://www.microsoft.com/software-download/windows{release}")
-    } else {
-        url
-    };
-    let urls = get_urls(&client, &skuid, &uuid, referer)?;
+    let urls = get_urls(&session.client, &skuid, &session.uuid, referer)?;
     let url = urls
         .into_iter()
         .map(|u| u.uri)
         .find(|u| u.contains(isotype))
         .ok_or(RidoError::URL)?;
-    Ok((url, None))
+
+    // The display name may carry the feature update (e.g. 'Windows 11 24H2'), and the ISO filename may carry it or the build
+    let mut version = WindowsVersion::from_text(&sku.product_display_name);
+    if let Some(filename) = reqwest::Url::parse(&url)
+        .ok()
+        .and_then(|u| u.path_segments()?.next_back().map(str::to_string))
+    {
+        version.merge(WindowsVersion::from_text(&filename));
+    }
+    Ok(ResolvedImage { url, hash: None, size: None, version })
+}
+
+/// Metadata for a product ID (also known as a product edition ID), as reported by Microsoft's download connector
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProductInfo {
+    pub product_id: u32,
+    /// e.g. 'Windows 11 24H2'
+    pub display_name: String,
+    /// The release the product belongs to, which determines its referer page and architectures
+    pub family: ConsumerRelease,
+    /// The feature update, if the display name includes one
+    pub version: WindowsVersion,
+    pub languages: Vec<ConsumerLanguage>,
+}
+
+impl ProductInfo {
+    /// Look up a product ID. IDs which Microsoft no longer serves fail with `ProductIdUnavailable`.
+    pub fn lookup(product_id: u32) -> Result<Self, RidoError> {
+        Session::new()?.product_info(product_id).map(|(info, _)| info)
+    }
+}
+
+/// A download connector session. The connector refuses requests whose session ID hasn't been registered first.
+struct Session {
+    client: reqwest::blocking::Client,
+    uuid: String,
+    user_agent: String,
+}
+
+impl Session {
+    fn new() -> Result<Self, RidoError> {
+        let user_agent = {
+            // Choose latest firefox release based on Firefox's 4 week release schedule
+            let unix_time = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .expect("System clock is broken");
+            let firefox_release = 124 + (unix_time.as_secs() - FIREFOX_124_RELEASE_TIME) / FOUR_WEEKS;
+            format!("Mozilla 5.0 (X11, Linux x86_64; rv:{firefox_release}.0) Gecko/20100101 Firefox/{firefox_release}.0")
+        };
+        let uuid = Uuid::new_v4().to_string();
+        let client = reqwest::blocking::Client::new();
+
+        client
+            .get(format!("https://vlscppe.microsoft.com/tags?org_id=y6jn8c31&session_id={uuid}",))
+            .header(ACCEPT, "")
+            .header(USER_AGENT, &user_agent)
+            .send()?;
+        Ok(Self { client, uuid, user_agent })
+    }
+
+    fn skus(&self, product_id: u32) -> Result<Vec<WindowsSku>, RidoError> {
+        get_skus(&self.client, product_id, &self.uuid)
+    }
+
+    fn product_info(&self, product_id: u32) -> Result<(ProductInfo, Vec<WindowsSku>), RidoError> {
+        let skus = self.skus(product_id)?;
+        let display_name = skus[0].product_display_name.clone();
+        let family = ConsumerRelease::family_of(&display_name).ok_or_else(|| RidoError::UnknownProduct(product_id, display_name.clone()))?;
+        let mut languages = Vec::new();
+        for sku in &skus {
+            if let Some(lang) = ConsumerLanguage::iter().find(|lang| lang.matches_sku(&sku.localized_language)) {
+                if !languages.contains(&lang) {
+                    languages.push(lang);
+                }
+            }
+        }
+        let info = ProductInfo {
+            product_id,
+            version: WindowsVersion::from_text(&display_name),
+            display_name,
+            family,
+            languages,
+        };
+        Ok((info, skus))
+    }
+}
+
+fn parse_product_id(html: &str) -> Option<u32> {
+    let document = Html::parse_document(html);
+    // The edition picker is a <select> whose options carry the product edition ID as their value, e.g.
+    // <option value="3113">Windows 11 (multi-edition ISO for x64 devices)</option>
+    let selector = Selector::parse("select option[value]").unwrap();
+    document
+        .select(&selector)
+        .filter(|option| option.text().collect::<String>().trim_start().starts_with("Windows"))
+        .filter_map(|option| option.value().attr("value"))
+        .map(str::trim)
+        .find_map(|value| value.parse().ok())
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "PascalCase")]
 struct SkuData {
+    #[serde(default)]
     skus: Vec<WindowsSku>,
+    #[serde(default)]
+    errors: Vec<ConnectorError>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConnectorError {
+    key: String,
 }
 
 #[derive(Deserialize)]
@@ -101,10 +232,21 @@ struct WindowsSku {
     localized_language: String,
 }
 
-fn get_skus(client: &reqwest::blocking::Client, product_id: &str, uuid: &str) -> Result<Vec<WindowsSku>, RidoError> {
+fn get_skus(client: &reqwest::blocking::Client, product_id: u32, uuid: &str) -> Result<Vec<WindowsSku>, RidoError> {
     let skuid_table_url = format!("https://www.microsoft.com/software-download-connector/api/getskuinformationbyproductedition?profile=606624d44113&ProductEditionId={}&SKU=undefined&friendlyFileName=undefined&Locale=en-US&sessionID={}", product_id,uuid);
     let skuid_table = client.get(skuid_table_url).send()?.text()?;
     let skuid_table: SkuData = serde_json::from_str(&skuid_table).map_err(RidoError::JSONParsing)?;
+    // Requests which Microsoft's bot protection rejects get a 'Sentinel' error, anything else means the product isn't served
+    if let Some(error) = skuid_table.errors.first() {
+        return Err(if error.key.contains("Sentinel") {
+            RidoError::BlockedRequest
+        } else {
+            RidoError::ProductIdUnavailable(product_id)
+        });
+    }
+    if skuid_table.skus.is_empty() {
+        return Err(RidoError::ProductIdUnavailable(product_id));
+    }
     Ok(skuid_table.skus)
 }
 
@@ -127,10 +269,17 @@ fn get_urls(client: &reqwest::blocking::Client, skuid: &str, uuid: &str, referer
     Ok(url.product_download_options)
 }
 
-#[derive(EnumIter, Debug, Copy, Clone, PartialEq)]
+#[derive(EnumIter, Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum ConsumerRelease {
     Eleven,
     Ten,
+    EightOne,
+    EightOneN,
+    EightOneSingleLanguage,
+    EightOneK,
+    EightOneKN,
+    // Not a release in its own right, so it's never listed
+    #[strum(disabled)]
     CustomProductID(u32),
 }
 
@@ -140,35 +289,100 @@ impl From<ConsumerRelease> for WindowsRelease {
     }
 }
 
+impl ConsumerRelease {
+    /// The release's identifier, as accepted by `TryFrom<&str>`
+    pub fn id(&self) -> String {
+        match self {
+            Self::Ten => "10".to_string(),
+            Self::Eleven => "11".to_string(),
+            Self::EightOne => "8.1".to_string(),
+            Self::EightOneN => "8.1-n".to_string(),
+            Self::EightOneSingleLanguage => "8.1-single-language".to_string(),
+            Self::EightOneK => "8.1-k".to_string(),
+            Self::EightOneKN => "8.1-kn".to_string(),
+            Self::CustomProductID(id) => format!("productid:{id}"),
+        }
+    }
+    /// The release's download page, which is also the referer the download connector expects
+    pub fn download_page(&self) -> Option<&'static str> {
+        match self {
+            Self::Ten => Some("https://microsoft.com/en-us/software-download/windows10ISO"),
+            Self::Eleven => Some("https://microsoft.com/en-us/software-download/windows11"),
+            Self::EightOne | Self::EightOneN | Self::EightOneSingleLanguage | Self::EightOneK | Self::EightOneKN => Some("https://www.microsoft.com/en-us/software-download/windows8ISO"),
+            Self::CustomProductID(_) => None,
+        }
+    }
+    /// The product ID, for releases which Microsoft no longer lists on their download page
+    fn product_id(&self) -> Option<u32> {
+        match self {
+            Self::EightOne => Some(52),
+            Self::EightOneN => Some(55),
+            Self::EightOneSingleLanguage => Some(48),
+            Self::EightOneK => Some(61),
+            Self::EightOneKN => Some(62),
+            Self::CustomProductID(id) => Some(*id),
+            Self::Ten | Self::Eleven => None,
+        }
+    }
+    /// The release a product belongs to, from its display name, e.g. 'Windows 11 24H2'
+    fn family_of(display_name: &str) -> Option<Self> {
+        let mut words = display_name.split_ascii_whitespace();
+        if words.next() != Some("Windows") {
+            return None;
+        }
+        match words.next()? {
+            "10" => Some(Self::Ten),
+            "11" => Some(Self::Eleven),
+            "8.1" => Some(Self::EightOne),
+            _ => None,
+        }
+    }
+    pub fn base_build(&self) -> Option<u32> {
+        match self {
+            Self::Ten => Some(10240),
+            Self::Eleven => Some(22000),
+            Self::EightOne | Self::EightOneN | Self::EightOneSingleLanguage | Self::EightOneK | Self::EightOneKN => Some(9600),
+            Self::CustomProductID(_) => None,
+        }
+    }
+    /// The release's human-readable name, e.g. 'Windows 11', which is also accepted by `TryFrom<&str>`
+    pub fn display_name(&self) -> String {
+        match self {
+            Self::Ten => "Windows 10".to_string(),
+            Self::Eleven => "Windows 11".to_string(),
+            Self::EightOne => "Windows 8.1".to_string(),
+            Self::EightOneN => "Windows 8.1 N".to_string(),
+            Self::EightOneSingleLanguage => "Windows 8.1 Single Language".to_string(),
+            Self::EightOneK => "Windows 8.1 K".to_string(),
+            Self::EightOneKN => "Windows 8.1 KN".to_string(),
+            Self::CustomProductID(id) => format!("Custom Product ID: {id}"),
+        }
+    }
+}
+
 impl fmt::Display for ConsumerRelease {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let text = match self {
-            ConsumerRelease::Ten => "Windows 10",
-            ConsumerRelease::Eleven => "Windows 11",
-            ConsumerRelease::CustomProductID(id) => &format!("Custom Product ID: {id}"),
-        };
-        write!(f, "{text}")
+        write!(f, "{}", self.display_name())
     }
 }
 
 impl TryFrom<&str> for ConsumerRelease {
     type Error = RidoError;
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        Ok(match value {
-            "10" => Self::Ten,
-            "11" => Self::Eleven,
-            _ => {
-                if let Some(Ok(product_id)) = value.strip_prefix("productid:").map(str::trim).map(str::parse) {
-                    Self::CustomProductID(product_id)
-                } else {
-                    return Err(RidoError::InvalidReleaseStr);
-                }
-            }
-        })
+        if let Some(release) = Self::iter().find(|release| release.id() == value || release.display_name().eq_ignore_ascii_case(value)) {
+            return Ok(release);
+        }
+        let product_id = value
+            .strip_prefix("productid:")
+            .or_else(|| value.strip_prefix("Custom Product ID:"));
+        match product_id.map(str::trim).map(str::parse) {
+            Some(Ok(product_id)) => Ok(Self::CustomProductID(product_id)),
+            _ => Err(RidoError::InvalidReleaseStr),
+        }
     }
 }
 
-#[derive(PartialEq, EnumIter, Debug, Copy, Clone)]
+#[derive(PartialEq, Eq, Hash, EnumIter, Debug, Copy, Clone)]
 pub enum ConsumerLanguage {
     Arabic,
     BrazilianPortuguese,
@@ -216,6 +430,56 @@ impl From<ConsumerLanguage> for WindowsLanguage {
     }
 }
 
+impl ConsumerLanguage {
+    /// Whether a SKU's language name refers to this language. Windows 8.1 SKUs call US English just 'English'.
+    fn matches_sku(&self, name: &str) -> bool {
+        self.to_string() == name || (*self == Self::EnglishUS && name == "English")
+    }
+    /// The language's locale tag, as used by Windows setup, e.g. 'en-US'
+    pub fn locale(&self) -> &'static str {
+        match self {
+            Self::Arabic => "ar-SA",
+            Self::BrazilianPortuguese => "pt-BR",
+            Self::Bulgarian => "bg-BG",
+            Self::Croatian => "hr-HR",
+            Self::Czech => "cs-CZ",
+            Self::Danish => "da-DK",
+            Self::Dutch => "nl-NL",
+            Self::EnglishInternational => "en-GB",
+            Self::EnglishUS => "en-US",
+            Self::Estonian => "et-EE",
+            Self::Finnish => "fi-FI",
+            Self::French => "fr-FR",
+            Self::FrenchCanadian => "fr-CA",
+            Self::German => "de-DE",
+            Self::Greek => "el-GR",
+            Self::Hebrew => "he-IL",
+            Self::Hungarian => "hu-HU",
+            Self::Italian => "it-IT",
+            Self::Japanese => "ja-JP",
+            Self::Korean => "ko-KR",
+            Self::Latvian => "lv-LV",
+            Self::Lithuanian => "lt-LT",
+            Self::MexicanSpanish => "es-MX",
+            Self::Norwegian => "nb-NO",
+            Self::Polish => "pl-PL",
+            Self::Portuguese => "pt-PT",
+            Self::Romanian => "ro-RO",
+            Self::Russian => "ru-RU",
+            Self::SerbianLatin => "sr-Latn-RS",
+            Self::SimplifiedChinese => "zh-CN",
+            Self::Slovak => "sk-SK",
+            Self::Slovenian => "sl-SI",
+            Self::Spanish => "es-ES",
+            Self::Swedish => "sv-SE",
+            Self::Thai => "th-TH",
+            Self::TraditionalChinese => "zh-TW",
+            Self::Turkish => "tr-TR",
+            Self::Ukrainian => "uk-UA",
+        }
+    }
+}
+
 impl fmt::Display for ConsumerLanguage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let text = match self {
@@ -311,7 +575,35 @@ impl TryFrom<&str> for ConsumerLanguage {
 
 impl ValidateLanguage for ConsumerLanguage {
     fn validate(&self, release: WindowsRelease) -> bool {
-        matches!(release, WindowsRelease::Consumer(_))
+        match release {
+            WindowsRelease::Consumer(release) => match release {
+                // French Canadian and Mexican Spanish media were introduced with Windows 10
+                ConsumerRelease::EightOne | ConsumerRelease::EightOneSingleLanguage => !matches!(self, Self::FrenchCanadian | Self::MexicanSpanish),
+                // N editions are only sold in Europe
+                ConsumerRelease::EightOneN => !matches!(
+                    self,
+                    Self::Arabic
+                        | Self::BrazilianPortuguese
+                        | Self::FrenchCanadian
+                        | Self::Hebrew
+                        | Self::Japanese
+                        | Self::Korean
+                        | Self::MexicanSpanish
+                        | Self::Russian
+                        | Self::SerbianLatin
+                        | Self::SimplifiedChinese
+                        | Self::Thai
+                        | Self::TraditionalChinese
+                        | Self::Turkish
+                        | Self::Ukrainian
+                ),
+                // K editions are only sold in Korea
+                ConsumerRelease::EightOneK | ConsumerRelease::EightOneKN => matches!(self, Self::Korean),
+                ConsumerRelease::Ten | ConsumerRelease::Eleven | ConsumerRelease::CustomProductID(_) => true,
+            },
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
     }
 }
 
@@ -320,3 +612,29 @@ impl ValidateWithArch for ConsumerRelease {
         !matches!(self, ConsumerRelease::Eleven if arch == WindowsArchitecture::i686)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn product_id_from_windows_10_page() {
+        assert_eq!(
+            parse_product_id(include_str!("../tests/fixtures/windows10ISO.html")),
+            Some(2618)
+        );
+    }
+
+    #[test]
+    fn product_id_from_windows_11_page() {
+        assert_eq!(parse_product_id(include_str!("../tests/fixtures/windows11.html")), Some(3113));
+    }
+
+    #[test]
+    fn product_id_missing() {
+        assert_eq!(
+            parse_product_id("<select><option value=\"\">Select edition</option></select>"),
+            None
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use crate::{checksum, remote, RidoError, WindowsData};
+use reqwest::{blocking::Client, header::RANGE, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+
+const BUFFER_SIZE: usize = 1024 * 1024;
+// Progress is persisted whenever a segment has advanced this far, so that little is lost when interrupted
+const SAVE_INTERVAL: u64 = 16 * 1024 * 1024;
+const MIN_SEGMENT_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Downloads an image over several connections at once, each fetching a byte range into a preallocated file.
+/// Progress is stored next to the destination in a `.rido-state` file, so that an interrupted download resumes
+/// where each segment left off.
+#[derive(Debug, Clone)]
+pub struct Downloader {
+    connections: usize,
+    retries: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct DownloadState {
+    size: u64,
+    etag: Option<String>,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Segment {
+    start: u64,
+    /// Exclusive
+    end: u64,
+    /// Next byte to be downloaded
+    position: u64,
+}
+
+impl Default for Downloader {
+    fn default() -> Self {
+        Self { connections: 8, retries: 5 }
+    }
+}
+
+impl Downloader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn connections(mut self, connections: usize) -> Self {
+        self.connections = connections.max(1);
+        self
+    }
+    /// Number of times each segment is retried before the download fails
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Download the image to `path`, verifying it against the image's hash if one is known
+    pub fn download(&self, data: &WindowsData, path: &Path) -> Result<(), RidoError> {
+        // Images in a directory mirror are simply copied
+        if let Some(source) = remote::file_path(data.download_url()) {
+            if !path.exists() || !same_file(&source, path)? {
+                fs::copy(&source, path)?;
+            }
+            return verify(data, path);
+        }
+
+        let client = Client::builder()
+            .timeout(None)
+            .connect_timeout(Duration::from_secs(30))
+            .build()?;
+        let metadata = match &data.remote {
+            Some(remote) if remote.content_length.is_some() => remote.clone(),
+            _ => remote::probe(&client, &data.url)?.metadata,
+        };
+        let size = metadata.content_length.ok_or(RidoError::UnknownSize)?;
+        let url = metadata.final_url;
+
+        let state_path = state_path(path);
+        let state = match load_state(&state_path) {
+            // The server's file must not have changed since the previous attempt
+            Some(state) if state.size == size && state.etag == metadata.etag && path.is_file() => state,
+            _ => {
+                let file = File::create(path)?;
+                file.set_len(size)?;
+                DownloadState {
+                    size,
+                    etag: metadata.etag,
+                    segments: split(size, self.connections),
+                }
+            }
+        };
+        save_state(&state_path, &state)?;
+
+        let state = Mutex::new(state);
+        let queue = Mutex::new((0..state.lock().unwrap().segments.len()).collect::<Vec<_>>());
+        let errors = thread::scope(|scope| {
+            let workers = (0..self.connections)
+                .map(|_| {
+                    scope.spawn(|| -> Result<(), RidoError> {
+                        let mut file = OpenOptions::new().write(true).open(path)?;
+                        loop {
+                            // Popping in its own statement releases the lock before the segment is downloaded
+                            let next = queue.lock().unwrap().pop();
+                            let Some(index) = next else { break };
+                            self.download_segment(&client, &url, &mut file, &state, &state_path, index)?;
+                        }
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .filter_map(|worker| worker.join().expect("Download worker panicked").err())
+                .collect::<Vec<_>>()
+        });
+        if let Some(error) = errors.into_iter().next() {
+            return Err(error);
+        }
+
+        // A mismatched image can't be trusted either, so the next attempt starts from scratch
+        fs::remove_file(&state_path)?;
+        verify(data, path)
+    }
+
+    fn download_segment(&self, client: &Client, url: &str, file: &mut File, state: &Mutex<DownloadState>, state_path: &Path, index: usize) -> Result<(), RidoError> {
+        let mut attempt = 0;
+        loop {
+            let segment = state.lock().unwrap().segments[index];
+            if segment.position >= segment.end {
+                return Ok(());
+            }
+            let mut last_saved = segment.position;
+            match fetch_range(client, url, file, segment, |position| {
+                let mut state = state.lock().unwrap();
+                state.segments[index].position = position;
+                if position == segment.end || position - last_saved >= SAVE_INTERVAL {
+                    save_state(state_path, &state)?;
+                    last_saved = position;
+                }
+                Ok(())
+            }) {
+                Ok(()) => attempt = 0,
+                Err(e) if attempt >= self.retries => return Err(e),
+                Err(_) => {
+                    attempt += 1;
+                    thread::sleep(Duration::from_secs(2u64.pow(attempt.min(5))));
+                }
+            }
+        }
+    }
+}
+
+fn verify(data: &WindowsData, path: &Path) -> Result<(), RidoError> {
+    if let Some(expected) = &data.hash {
+        let actual = checksum::sha256_file(path)?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(RidoError::HashMismatch { expected: expected.clone(), actual });
+        }
+    }
+    Ok(())
+}
+
+fn same_file(a: &Path, b: &Path) -> Result<bool, RidoError> {
+    Ok(a.canonicalize()? == b.canonicalize()?)
+}
+
+fn fetch_range(client: &Client, url: &str, file: &mut File, segment: Segment, mut progress: impl FnMut(u64) -> Result<(), RidoError>) -> Result<(), RidoError> {
+    let mut response = client
+        .get(url)
+        .header(RANGE, format!("bytes={}-{}", segment.position, segment.end - 1))
+        .send()?
+        .error_for_status()?;
+    // A 200 response would contain the whole file, and writing it at this segment's offset would corrupt the image
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Err(RidoError::RangesUnsupported);
+    }
+
+    file.seek(SeekFrom::Start(segment.position))?;
+    let mut position = segment.position;
+    let mut buffer = vec![0; BUFFER_SIZE];
+    while position < segment.end {
+        let wanted = BUFFER_SIZE.min((segment.end - position) as usize);
+        let read = response.read(&mut buffer[..wanted])?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buffer[..read])?;
+        position += read as u64;
+        progress(position)?;
+    }
+    if position < segment.end {
+        return Err(RidoError::IO(std::io::ErrorKind::UnexpectedEof.into()));
+    }
+    Ok(())
+}
+
+fn split(size: u64, connections: usize) -> Vec<Segment> {
+    // More segments than connections keeps every connection busy until the end
+    let count = (connections as u64 * 4).max(1);
+    let segment_size = size.div_ceil(count).max(MIN_SEGMENT_SIZE);
+    (0..size)
+        .step_by(segment_size as usize)
+        .map(|start| Segment {
+            start,
+            end: (start + segment_size).min(size),
+            position: start,
+        })
+        .collect()
+}
+
+fn state_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".rido-state");
+    path.with_file_name(name)
+}
+
+fn load_state(path: &Path) -> Option<DownloadState> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn save_state(path: &Path, state: &DownloadState) -> Result<(), RidoError> {
+    let json = serde_json::to_string(state).map_err(RidoError::JSONParsing)?;
+    // Write to a temporary file first, so that an interruption never leaves a truncated state file
+    let temp = path.with_extension("rido-state.tmp");
+    fs::write(&temp, json)?;
+    fs::rename(temp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WindowsEntry;
+    use std::{
+        io::BufRead,
+        net::{TcpListener, TcpStream},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    const SIZE: usize = 4 * MIN_SEGMENT_SIZE as usize;
+
+    /// A local HTTP server which serves ranges of `content` slowly enough for requests to overlap
+    #[derive(Default)]
+    struct RangeServer {
+        content: Vec<u8>,
+        active: AtomicUsize,
+        max_active: AtomicUsize,
+        /// Cut off the response for the segment starting at this offset, once
+        interrupt_at: Mutex<Option<u64>>,
+        ranges: Mutex<Vec<String>>,
+    }
+
+    impl RangeServer {
+        fn start(content: Vec<u8>, interrupt_at: Option<u64>) -> (Arc<Self>, String) {
+            let server = Arc::new(Self {
+                content,
+                interrupt_at: Mutex::new(interrupt_at),
+                ..Default::default()
+            });
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!(
+                "http://{}/26100.1742.240906-0331.ge_release_svc_refresh_CLIENT_CONSUMER_x64FRE_en-us.iso",
+                listener.local_addr().unwrap()
+            );
+            let accepting = server.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let server = accepting.clone();
+                    thread::spawn(move || server.respond(stream));
+                }
+            });
+            (server, url)
+        }
+
+        fn respond(&self, mut stream: TcpStream) {
+            // Only the Range header matters, and HEAD requests are told the size without a body
+            let mut range = None;
+            for line in std::io::BufReader::new(stream.try_clone().unwrap()).lines() {
+                let line = line.unwrap_or_default().to_ascii_lowercase();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((start, end)) = line.strip_prefix("range: bytes=").and_then(|value| value.split_once('-')) {
+                    range = Some((start.parse::<u64>().unwrap(), end.parse::<u64>().unwrap()));
+                }
+            }
+            let total = self.content.len();
+            let Some((start, end)) = range else {
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {total}\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n"
+                );
+                return;
+            };
+            self.ranges.lock().unwrap().push(format!("{start}-{end}"));
+
+            let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_active.fetch_max(active, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(300));
+            let body = &self.content[start as usize..=end as usize];
+            let _ = write!(
+                stream,
+                "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {start}-{end}/{total}\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let interrupt = {
+                let mut interrupt_at = self.interrupt_at.lock().unwrap();
+                interrupt_at.take_if(|offset| *offset == start).is_some()
+            };
+            let _ = stream.write_all(if interrupt { &body[..body.len() / 2] } else { body });
+            self.active.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    fn image(url: String) -> WindowsData {
+        WindowsData {
+            info: WindowsEntry::list_all().remove(0),
+            url,
+            hash: None,
+            size: None,
+            version: Default::default(),
+            remote: None,
+        }
+    }
+
+    fn content() -> Vec<u8> {
+        (0..SIZE).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn destination(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rido-download-{name}-{}.iso", std::process::id()))
+    }
+
+    #[test]
+    fn segments_download_concurrently() {
+        let (server, url) = RangeServer::start(content(), None);
+        let path = destination("concurrent");
+        Downloader::new().connections(4).download(&image(url), &path).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), server.content);
+        assert_eq!(server.ranges.lock().unwrap().len(), 4);
+        assert!(
+            server.max_active.load(Ordering::SeqCst) > 1,
+            "segments were downloaded one at a time"
+        );
+        assert!(!state_path(&path).exists());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn interrupted_download_resumes() {
+        let (server, url) = RangeServer::start(content(), Some(MIN_SEGMENT_SIZE));
+        let path = destination("resume");
+        let data = image(url);
+        let downloader = Downloader::new().connections(4).retries(0);
+        assert!(downloader.download(&data, &path).is_err());
+        assert!(state_path(&path).exists());
+
+        // Only what's left of the interrupted segment is fetched again
+        server.ranges.lock().unwrap().clear();
+        downloader.download(&data, &path).unwrap();
+        let ranges = server.ranges.lock().unwrap().clone();
+        let [range] = ranges.as_slice() else { panic!("expected a single range, got {ranges:?}") };
+        let (start, end) = range.split_once('-').unwrap();
+        assert!(
+            (MIN_SEGMENT_SIZE..2 * MIN_SEGMENT_SIZE).contains(&start.parse().unwrap()),
+            "{range}"
+        );
+        assert_eq!(end.parse::<u64>().unwrap(), 2 * MIN_SEGMENT_SIZE - 1);
+        assert_eq!(fs::read(&path).unwrap(), server.content);
+        assert!(!state_path(&path).exists());
+        fs::remove_file(path).unwrap();
+    }
+}
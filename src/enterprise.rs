@@ -1,12 +1,52 @@
-use crate::{RidoError, ValidateLanguage, ValidateWithArch, WindowsArchitecture, WindowsLanguage, WindowsRelease};
-use regex::Regex;
+use crate::{Provider, ProviderCapabilities, ResolvedImage, RidoError, ValidateLanguage, ValidateWithArch, WindowsArchitecture, WindowsEntry, WindowsLanguage, WindowsRelease, WindowsVersion};
+use reqwest::Url;
+use scraper::{ElementRef, Html};
 use std::fmt;
+use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
-pub fn get_enterprise_info(release: EnterpriseRelease, lang: EnterpriseLanguage, arch: WindowsArchitecture) -> Result<(String, Option<String>), RidoError> {
-    if !lang.validate(release.into()) {
-        return Err(RidoError::InvalidLanguage(release.into(), lang.into()));
+pub struct EnterpriseProvider;
+
+impl Provider for EnterpriseProvider {
+    fn name(&self) -> &str {
+        "enterprise"
+    }
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            hashes: false,
+            versions: true,
+            redirects: true,
+            network: true,
+        }
+    }
+    fn entries(&self) -> Vec<WindowsEntry> {
+        EnterpriseRelease::iter()
+            .flat_map(|rel| {
+                let release: WindowsRelease = rel.into();
+                EnterpriseLanguage::iter()
+                    .filter(move |&lang| lang.validate(release))
+                    .flat_map(move |lang| {
+                        let lang = lang.into();
+                        WindowsArchitecture::iter()
+                            .filter(move |&arch| rel.validate(arch))
+                            .map(move |arch| WindowsEntry { release, lang, arch })
+                    })
+            })
+            .collect()
+    }
+    fn supports(&self, entry: &WindowsEntry) -> bool {
+        matches!(entry.release, WindowsRelease::Enterprise(_))
     }
+    fn resolve(&self, entry: &WindowsEntry) -> Result<ResolvedImage, RidoError> {
+        match (entry.release, entry.lang) {
+            (WindowsRelease::Enterprise(release), WindowsLanguage::Enterprise(lang)) => get_enterprise_info(release, lang, entry.arch),
+            #[allow(unreachable_patterns)]
+            _ => Err(RidoError::InvalidSelection),
+        }
+    }
+}
+
+pub fn get_enterprise_info(release: EnterpriseRelease, lang: EnterpriseLanguage, arch: WindowsArchitecture) -> Result<ResolvedImage, RidoError> {
     let valid_release = match release {
         EnterpriseRelease::TenEnterprise | EnterpriseRelease::TenLtsc => "windows-10-enterprise",
         EnterpriseRelease::ElevenEnterprise => "windows-11-enterprise",
@@ -25,45 +65,153 @@ pub fn get_enterprise_info(release: EnterpriseRelease, lang: EnterpriseLanguage,
         return Err(RidoError::EmptyResponse);
     }
 
-    let (culture, country) = match lang {
-        EnterpriseLanguage::BrazilianPortuguese => ("pt-br", "BR"),
-        EnterpriseLanguage::EnglishGB => ("en-gb", "GB"),
-        EnterpriseLanguage::EnglishUS => ("en-us", "US"),
-        EnterpriseLanguage::French => ("fr-fr", "FR"),
-        EnterpriseLanguage::German => ("de-de", "DE"),
-        EnterpriseLanguage::Italian => ("it-it", "IT"),
-        EnterpriseLanguage::Japanese => ("ja-jp", "JP"),
-        EnterpriseLanguage::Korean => ("ko-kr", "KR"),
-        EnterpriseLanguage::Russian => ("ru-ru", "RU"),
-        EnterpriseLanguage::SimplifiedChinese => ("zh-cn", "CN"),
-        EnterpriseLanguage::Spanish => ("es-es", "ES"),
-        EnterpriseLanguage::TraditionalChinese => ("zh-tw", "TW"),
-    };
+    select_download(&download_page_html, release, lang, arch)
+}
+
+/// Pick the download link for an entry from its evaluation center page
+fn select_download(html: &str, release: EnterpriseRelease, lang: EnterpriseLanguage, arch: WindowsArchitecture) -> Result<ResolvedImage, RidoError> {
+    // Download links identify the language as e.g. 'culture=pt-br&country=BR'
+    let culture = lang.locale().to_ascii_lowercase();
+    let country = lang.locale().rsplit('-').next().unwrap_or_default();
 
     let bits = match arch {
         WindowsArchitecture::i686 => "32",
         WindowsArchitecture::x86_64 => "64",
     };
 
-    let iso_regex = Regex::new(r#"href="(https://go\.microsoft\.com/fwlink/p/\?LinkID=\d{7}&clcid=0x\w{3}&culture=([a-z]{2}-[a-z]{2})&country=(\w{2}))">\s(64|32)-bit"#).unwrap();
-
-    let urls = iso_regex
-        .captures_iter(&download_page_html)
-        .filter(|c| c[2] == *culture && c[3] == *country && c[4] == *bits)
+    let wants_ltsc = release == EnterpriseRelease::TenLtsc;
+    let links = parse_download_links(html)
+        .into_iter()
+        .filter(|link| link.culture == culture && link.country == country && link.bits == bits)
+        // Not every section says 'ISO', so only media which is known to be something else is excluded
+        .filter(|link| link.is_ltsc() == wants_ltsc && link.media() != MediaKind::Vhd && !link.is_arm())
         .collect::<Vec<_>>();
 
-    let url_capture = match release {
-        EnterpriseRelease::TenLtsc => urls.get(1),
-        _ => urls.first(),
+    let section = format!("{release} {bits}-bit ISO");
+    let link = links.first().ok_or_else(|| RidoError::MissingSection(section.clone()))?;
+    // Every remaining link must come from the same section; otherwise we can't be sure which image was meant
+    if links.iter().any(|other| other.section != link.section) {
+        return Err(RidoError::AmbiguousSection(section));
+    }
+
+    let url = link.url.clone();
+    // Builds are only known once the redirect is resolved, but the page may name the feature update
+    let mut version = WindowsVersion::from_text(&link.label);
+    version.merge(WindowsVersion::from_text(&link.section));
+
+    Ok(ResolvedImage { url, hash: None, size: None, version })
+}
+
+struct DownloadLink {
+    url: String,
+    culture: String,
+    country: String,
+    bits: String,
+    section: String,
+    label: String,
+}
+
+#[derive(PartialEq, Debug)]
+enum MediaKind {
+    Iso,
+    Vhd,
+    Unknown,
+}
+
+impl DownloadLink {
+    fn mentions(&self, keyword: &str) -> bool {
+        [&self.section, &self.label].into_iter().any(|text| has_word(text, keyword))
+    }
+    fn is_ltsc(&self) -> bool {
+        self.mentions("LTSC")
+    }
+    fn is_arm(&self) -> bool {
+        self.mentions("ARM64") || self.mentions("ARM")
+    }
+    fn media(&self) -> MediaKind {
+        // Labels are more specific than section headings, so they take precedence
+        for text in [&self.label, &self.section] {
+            if has_word(text, "VHD") || has_word(text, "VHDX") {
+                return MediaKind::Vhd;
+            } else if has_word(text, "ISO") {
+                return MediaKind::Iso;
+            }
+        }
+        MediaKind::Unknown
+    }
+}
+
+fn has_word(text: &str, word: &str) -> bool {
+    text.split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|w| w.eq_ignore_ascii_case(word))
+}
+
+fn parse_download_links(html: &str) -> Vec<DownloadLink> {
+    let document = Html::parse_document(html);
+    let mut headings: [String; 6] = Default::default();
+    let mut links = Vec::new();
+
+    // Walk the document in order, so that each link is associated with the chain of headings above it.
+    // e.g. a link below <h2>Windows 10 Enterprise LTSC</h2><h3>ISO downloads</h3> is in section
+    // 'Windows 10 Enterprise LTSC / ISO downloads'
+    for element in document.root_element().descendants().filter_map(ElementRef::wrap) {
+        let name = element.value().name();
+        if let Some(level) = name
+            .strip_prefix('h')
+            .and_then(|level| level.parse::<usize>().ok())
+            .filter(|level| (1..=6).contains(level))
+        {
+            headings[level - 1] = collapse_whitespace(element.text());
+            headings[level..].iter_mut().for_each(String::clear);
+        } else if name == "a" {
+            let section = headings
+                .iter()
+                .filter(|h| !h.is_empty())
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(" / ");
+            links.extend(parse_download_link(element, &section));
+        }
+    }
+    links
+}
+
+fn parse_download_link(anchor: ElementRef, section: &str) -> Option<DownloadLink> {
+    let href = anchor.value().attr("href")?.trim();
+    let parsed = Url::parse(href).ok()?;
+    if parsed.host_str() != Some("go.microsoft.com") || !parsed.path().starts_with("/fwlink") {
+        return None;
     }
-    .ok_or(RidoError::HTMLParse)?;
+    let query = |key: &str| {
+        parsed
+            .query_pairs()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.into_owned())
+    };
+    query("linkid")?;
+    let culture = query("culture")?.to_ascii_lowercase();
+    let country = query("country")?.to_ascii_uppercase();
 
-    let url = url_capture[1].to_string();
+    // Link text is formatted as '<bits>-bit edition', e.g. '64-bit edition'
+    let text = collapse_whitespace(anchor.text());
+    let bits = text.split_once("-bit")?.0.trim().to_string();
+    let label = anchor.value().attr("aria-label").map(str::to_string).unwrap_or(text);
+
+    Some(DownloadLink {
+        url: href.to_string(),
+        culture,
+        country,
+        bits,
+        section: section.to_string(),
+        label,
+    })
+}
 
-    Ok((url, None))
+fn collapse_whitespace<'a>(text: impl Iterator<Item = &'a str>) -> String {
+    text.flat_map(str::split_whitespace).collect::<Vec<_>>().join(" ")
 }
 
-#[derive(PartialEq, EnumIter, Debug, Copy, Clone)]
+#[derive(PartialEq, Eq, Hash, EnumIter, Debug, Copy, Clone)]
 pub enum EnterpriseRelease {
     ElevenEnterprise,
     TenEnterprise,
@@ -80,18 +228,49 @@ impl From<EnterpriseRelease> for WindowsRelease {
     }
 }
 
+impl EnterpriseRelease {
+    /// The release's identifier, as accepted by `TryFrom<&str>`
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::TenEnterprise => "10-enterprise",
+            Self::TenLtsc => "10-ltsc",
+            Self::ElevenEnterprise => "11-enterprise",
+            Self::Server2012R2 => "server-2012-r2",
+            Self::Server2016 => "server-2016",
+            Self::Server2019 => "server-2019",
+            Self::Server2022 => "server-2022",
+        }
+    }
+    pub fn base_build(&self) -> u32 {
+        match self {
+            Self::Server2012R2 => 9600,
+            Self::TenEnterprise | Self::TenLtsc => 10240,
+            Self::Server2016 => 14393,
+            Self::Server2019 => 17763,
+            Self::Server2022 => 20348,
+            Self::ElevenEnterprise => 22000,
+        }
+    }
+    pub fn is_server(&self) -> bool {
+        !matches!(self, Self::TenEnterprise | Self::TenLtsc | Self::ElevenEnterprise)
+    }
+    /// The release's human-readable name, e.g. 'Windows Server 2022', which is also accepted by `TryFrom<&str>`
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Self::TenEnterprise => "Windows 10 Enterprise",
+            Self::TenLtsc => "Windows 10 LTSC",
+            Self::ElevenEnterprise => "Windows 11 Enterprise",
+            Self::Server2012R2 => "Windows Server 2012 R2",
+            Self::Server2016 => "Windows Server 2016",
+            Self::Server2019 => "Windows Server 2019",
+            Self::Server2022 => "Windows Server 2022",
+        }
+    }
+}
+
 impl fmt::Display for EnterpriseRelease {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let text = match self {
-            EnterpriseRelease::TenEnterprise => "Windows 10 Enterprise",
-            EnterpriseRelease::TenLtsc => "Windows 10 LTSC",
-            EnterpriseRelease::ElevenEnterprise => "Windows 11 Enterprise",
-            EnterpriseRelease::Server2012R2 => "Windows Server 2012 R2",
-            EnterpriseRelease::Server2016 => "Windows Server 2016",
-            EnterpriseRelease::Server2019 => "Windows Server 2019",
-            EnterpriseRelease::Server2022 => "Windows Server 2022",
-        };
-        write!(f, "{text}")
+        write!(f, "{}", self.display_name())
     }
 }
 
@@ -106,12 +285,16 @@ impl TryFrom<&str> for EnterpriseRelease {
             "server-2016" => Self::Server2016,
             "server-2019" => Self::Server2019,
             "server-2022" => Self::Server2022,
-            _ => return Err(RidoError::InvalidReleaseStr),
+            _ => {
+                return Self::iter()
+                    .find(|release| release.display_name().eq_ignore_ascii_case(value))
+                    .ok_or(RidoError::InvalidReleaseStr)
+            }
         })
     }
 }
 
-#[derive(PartialEq, EnumIter, Debug, Copy, Clone)]
+#[derive(PartialEq, Eq, Hash, EnumIter, Debug, Copy, Clone)]
 pub enum EnterpriseLanguage {
     BrazilianPortuguese,
     EnglishUS,
@@ -133,6 +316,26 @@ impl From<EnterpriseLanguage> for WindowsLanguage {
     }
 }
 
+impl EnterpriseLanguage {
+    /// The language's locale tag, as used by Windows setup, e.g. 'en-US'
+    pub fn locale(&self) -> &'static str {
+        match self {
+            Self::BrazilianPortuguese => "pt-BR",
+            Self::EnglishUS => "en-US",
+            Self::EnglishGB => "en-GB",
+            Self::French => "fr-FR",
+            Self::German => "de-DE",
+            Self::Italian => "it-IT",
+            Self::Japanese => "ja-JP",
+            Self::Korean => "ko-KR",
+            Self::Russian => "ru-RU",
+            Self::SimplifiedChinese => "zh-CN",
+            Self::Spanish => "es-ES",
+            Self::TraditionalChinese => "zh-TW",
+        }
+    }
+}
+
 impl fmt::Display for EnterpriseLanguage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let text = match self {
@@ -199,3 +402,199 @@ impl ValidateWithArch for EnterpriseRelease {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEN_ENTERPRISE: &str = include_str!("../tests/fixtures/evalcenter-windows-10-enterprise.html");
+
+    #[test]
+    fn download_links_from_windows_10_enterprise_page() {
+        let links = parse_download_links(TEN_ENTERPRISE);
+        let summary = links
+            .iter()
+            .map(|link| {
+                (
+                    link.culture.as_str(),
+                    link.country.as_str(),
+                    link.bits.as_str(),
+                    link.url.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "en-us",
+                    "US",
+                    "32",
+                    "https://go.microsoft.com/fwlink/p/?LinkID=2208843&clcid=0x409&culture=en-us&country=US"
+                ),
+                (
+                    "en-us",
+                    "US",
+                    "64",
+                    "https://go.microsoft.com/fwlink/p/?LinkID=2208844&clcid=0x409&culture=en-us&country=US"
+                ),
+                (
+                    "de-de",
+                    "DE",
+                    "32",
+                    "https://go.microsoft.com/fwlink/p/?LinkID=2208843&clcid=0x407&culture=de-de&country=DE"
+                ),
+                (
+                    "de-de",
+                    "DE",
+                    "64",
+                    "https://go.microsoft.com/fwlink/p/?LinkID=2208844&clcid=0x407&culture=de-de&country=DE"
+                ),
+                (
+                    "en-us",
+                    "US",
+                    "32",
+                    "https://go.microsoft.com/fwlink/p/?LinkID=2195404&clcid=0x409&culture=en-us&country=US"
+                ),
+                (
+                    "en-us",
+                    "US",
+                    "64",
+                    "https://go.microsoft.com/fwlink/p/?LinkID=2195280&clcid=0x409&culture=en-us&country=US"
+                ),
+            ]
+        );
+        assert_eq!(
+            links[0].section,
+            "Windows 10 Enterprise / Windows 10 Enterprise / ISO – Enterprise downloads"
+        );
+        assert_eq!(
+            links[5].section,
+            "Windows 10 Enterprise / Windows 10 Enterprise LTSC 2021 / ISO – LTSC downloads"
+        );
+        assert!(!links[0].is_ltsc() && links[5].is_ltsc());
+    }
+
+    #[test]
+    fn download_links_from_server_page() {
+        let links = parse_download_links(include_str!("../tests/fixtures/evalcenter-windows-server-2022.html"));
+        assert_eq!(links.len(), 3);
+        assert_eq!(
+            (links[1].culture.as_str(), links[1].country.as_str(), links[1].bits.as_str()),
+            ("fr-fr", "FR", "64")
+        );
+        assert_eq!(links[0].media(), MediaKind::Iso);
+        assert_eq!(links[2].media(), MediaKind::Vhd);
+    }
+
+    #[test]
+    fn ltsc_and_enterprise_pick_their_own_sections() {
+        let enterprise = select_download(
+            TEN_ENTERPRISE,
+            EnterpriseRelease::TenEnterprise,
+            EnterpriseLanguage::EnglishUS,
+            WindowsArchitecture::x86_64,
+        )
+        .unwrap();
+        let ltsc = select_download(
+            TEN_ENTERPRISE,
+            EnterpriseRelease::TenLtsc,
+            EnterpriseLanguage::EnglishUS,
+            WindowsArchitecture::x86_64,
+        )
+        .unwrap();
+        assert_eq!(
+            enterprise.url,
+            "https://go.microsoft.com/fwlink/p/?LinkID=2208844&clcid=0x409&culture=en-us&country=US"
+        );
+        assert_eq!(
+            ltsc.url,
+            "https://go.microsoft.com/fwlink/p/?LinkID=2195280&clcid=0x409&culture=en-us&country=US"
+        );
+
+        let ltsc_32 = select_download(
+            TEN_ENTERPRISE,
+            EnterpriseRelease::TenLtsc,
+            EnterpriseLanguage::EnglishUS,
+            WindowsArchitecture::i686,
+        )
+        .unwrap();
+        assert_eq!(
+            ltsc_32.url,
+            "https://go.microsoft.com/fwlink/p/?LinkID=2195404&clcid=0x409&culture=en-us&country=US"
+        );
+        let german = select_download(
+            TEN_ENTERPRISE,
+            EnterpriseRelease::TenEnterprise,
+            EnterpriseLanguage::German,
+            WindowsArchitecture::i686,
+        )
+        .unwrap();
+        assert_eq!(
+            german.url,
+            "https://go.microsoft.com/fwlink/p/?LinkID=2208843&clcid=0x407&culture=de-de&country=DE"
+        );
+    }
+
+    #[test]
+    fn server_skips_vhd() {
+        let html = include_str!("../tests/fixtures/evalcenter-windows-server-2022.html");
+        let image = select_download(
+            html,
+            EnterpriseRelease::Server2022,
+            EnterpriseLanguage::EnglishUS,
+            WindowsArchitecture::x86_64,
+        )
+        .unwrap();
+        assert_eq!(
+            image.url,
+            "https://go.microsoft.com/fwlink/p/?LinkID=2195280&clcid=0x409&culture=en-us&country=US"
+        );
+    }
+
+    #[test]
+    fn missing_section_is_named() {
+        // The LTSC section has no German downloads
+        let error = select_download(
+            TEN_ENTERPRISE,
+            EnterpriseRelease::TenLtsc,
+            EnterpriseLanguage::German,
+            WindowsArchitecture::x86_64,
+        )
+        .unwrap_err();
+        assert!(
+            matches!(&error, RidoError::MissingSection(section) if section == "Windows 10 LTSC 64-bit ISO"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn links_without_media_keywords_are_kept() {
+        let html = r#"<h2>Windows Server 2019</h2><a href="https://go.microsoft.com/fwlink/p/?LinkID=1&culture=en-us&country=US">64-bit edition</a>"#;
+        let image = select_download(
+            html,
+            EnterpriseRelease::Server2019,
+            EnterpriseLanguage::EnglishUS,
+            WindowsArchitecture::x86_64,
+        )
+        .unwrap();
+        assert_eq!(
+            image.url,
+            "https://go.microsoft.com/fwlink/p/?LinkID=1&culture=en-us&country=US"
+        );
+    }
+
+    #[test]
+    fn unlabelled_ltsc_section_is_ambiguous() {
+        // If the LTSC section isn't labelled, both sections match and neither is picked
+        let html = TEN_ENTERPRISE.replace("LTSC", "Long-term");
+        let error = select_download(
+            &html,
+            EnterpriseRelease::TenEnterprise,
+            EnterpriseLanguage::EnglishUS,
+            WindowsArchitecture::x86_64,
+        )
+        .unwrap_err();
+        assert!(matches!(error, RidoError::AmbiguousSection(_)), "{error}");
+    }
+}
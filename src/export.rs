@@ -0,0 +1,92 @@
+use crate::WindowsData;
+use std::fmt::Write;
+
+/// Generate a Metalink v4 (RFC 5854) document describing the given images
+pub fn metalink(images: &[WindowsData]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<metalink xmlns=\"urn:ietf:params:xml:ns:metalink\">\n");
+    for image in images {
+        let _ = writeln!(xml, "  <file name=\"{}\">", escape_xml(&image.filename()));
+        let description = format!("{} ({}, {})", image.info.release, image.info.lang, image.info.arch);
+        let _ = writeln!(xml, "    <description>{}</description>", escape_xml(&description));
+        if let Some(size) = image.size {
+            let _ = writeln!(xml, "    <size>{size}</size>");
+        }
+        if let Some(hash) = &image.hash {
+            let _ = writeln!(xml, "    <hash type=\"sha-256\">{}</hash>", hash.to_ascii_lowercase());
+        }
+        let _ = writeln!(xml, "    <url>{}</url>", escape_xml(image.download_url()));
+        xml.push_str("  </file>\n");
+    }
+    xml.push_str("</metalink>\n");
+    xml
+}
+
+/// Generate an aria2 input file (for use with `aria2c --input-file`) for the given images
+pub fn aria2_input(images: &[WindowsData]) -> String {
+    let mut input = String::new();
+    for image in images {
+        let _ = writeln!(input, "{}", image.download_url());
+        let _ = writeln!(input, "  out={}", image.filename());
+        if let Some(hash) = &image.hash {
+            let _ = writeln!(input, "  checksum=sha-256={}", hash.to_ascii_lowercase());
+        }
+    }
+    input
+}
+
+pub(crate) fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RemoteMetadata, WindowsEntry};
+
+    fn data() -> WindowsData {
+        let url = "https://software.download.prss.microsoft.com/dbazure/Win11.iso?t=1&e=2".to_string();
+        WindowsData {
+            info: WindowsEntry::list_all().remove(0),
+            url: url.clone(),
+            hash: Some("B56B911BF18A2CEAEB3904D87E7C770BDF92D3099599D61AC2497B91BF190B11".to_string()),
+            size: Some(5819484160),
+            version: Default::default(),
+            remote: Some(RemoteMetadata {
+                final_url: url,
+                filename: Some("Win11 <24H2> & \"x64\".iso".to_string()),
+                content_length: Some(5819484160),
+                last_modified: None,
+                etag: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn metalink_file() {
+        let xml = metalink(&[data()]);
+        assert!(xml.contains("  <file name=\"Win11 &lt;24H2&gt; &amp; &quot;x64&quot;.iso\">\n"));
+        assert!(xml.contains("    <size>5819484160</size>\n"));
+        assert!(xml.contains("    <hash type=\"sha-256\">b56b911bf18a2ceaeb3904d87e7c770bdf92d3099599d61ac2497b91bf190b11</hash>\n"));
+        assert!(xml.contains("    <url>https://software.download.prss.microsoft.com/dbazure/Win11.iso?t=1&amp;e=2</url>\n"));
+        roxmltree::Document::parse(&xml).unwrap();
+    }
+
+    #[test]
+    fn aria2_input_file() {
+        assert_eq!(
+            aria2_input(&[data()]),
+            "https://software.download.prss.microsoft.com/dbazure/Win11.iso?t=1&e=2\n  out=Win11 <24H2> & \"x64\".iso\n  checksum=sha-256=b56b911bf18a2ceaeb3904d87e7c770bdf92d3099599d61ac2497b91bf190b11\n"
+        );
+    }
+}
@@ -0,0 +1,691 @@
+use crate::{RidoError, WindowsArchitecture, WindowsEntry, WindowsLanguage, WindowsRelease, WindowsVersion};
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+const SECTOR_SIZE: u64 = 2048;
+// XML resources larger than this almost certainly aren't WIM metadata, and shouldn't be read into memory
+const MAX_XML_SIZE: u64 = 64 * 1024 * 1024;
+// Likewise for directories, which are read whole
+const MAX_DIRECTORY_SIZE: u64 = 16 * 1024 * 1024;
+
+/// What a local ISO contains, according to the metadata in its `sources/install.wim` or `sources/install.esd`
+#[derive(Debug, Clone, PartialEq)]
+pub struct IsoIdentity {
+    /// The entry the ISO corresponds to, if its release, language and architecture are all recognised
+    pub entry: Option<WindowsEntry>,
+    pub version: WindowsVersion,
+    pub editions: Vec<IsoEdition>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IsoEdition {
+    pub index: u32,
+    pub name: String,
+    /// e.g. 'Professional' or 'ServerStandard'
+    pub edition_id: Option<String>,
+    /// 'Client' or 'Server'
+    pub installation_type: Option<String>,
+    pub arch: Option<WindowsArchitecture>,
+    /// Locale tags, e.g. 'en-US'
+    pub languages: Vec<String>,
+    pub build: Option<u32>,
+    pub revision: Option<u32>,
+}
+
+impl IsoIdentity {
+    /// Read the ISO9660 or UDF filesystem of an ISO, and identify it from its install image metadata
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, RidoError> {
+        let mut file = File::open(path)?;
+        let install_image = ["install.wim", "install.esd"]
+            .into_iter()
+            .find_map(|name| find_file(&mut file, &["sources", name]).transpose())
+            .ok_or_else(|| iso_error("no sources/install.wim or sources/install.esd"))??;
+        let xml = read_wim_xml(&mut file, &install_image)?;
+        let editions = parse_wim_xml(&xml)?;
+        Ok(Self::from_editions(editions))
+    }
+
+    pub fn from_editions(editions: Vec<IsoEdition>) -> Self {
+        let mut version = WindowsVersion::default();
+        if let Some(edition) = editions.first() {
+            version.merge(WindowsVersion {
+                build: edition.build,
+                revision: edition.revision,
+                ..Default::default()
+            });
+        }
+        let entry = editions.first().and_then(|edition| identify_entry(edition, &editions));
+        Self { entry, version, editions }
+    }
+}
+
+fn identify_entry(first: &IsoEdition, editions: &[IsoEdition]) -> Option<WindowsEntry> {
+    let arch = first.arch?;
+    let build = first.build?;
+    let locale = first.languages.first()?;
+    let is_server = first.installation_type.as_deref().is_some_and(|t| t.starts_with("Server"));
+    let is_eval = editions
+        .iter()
+        .any(|e| e.edition_id.as_deref().is_some_and(|id| id.ends_with("Eval")));
+
+    let release: WindowsRelease = if is_server || is_eval { enterprise_release(build, editions)? } else { consumer_release(build)? };
+    let lang = language_for_locale(release, locale)?;
+    Some(WindowsEntry { release, lang, arch })
+}
+
+#[cfg(feature = "consumer")]
+fn consumer_release(build: u32) -> Option<WindowsRelease> {
+    use crate::ConsumerRelease;
+    Some(
+        match build {
+            22000.. => ConsumerRelease::Eleven,
+            10240.. => ConsumerRelease::Ten,
+            9600 => ConsumerRelease::EightOne,
+            _ => return None,
+        }
+        .into(),
+    )
+}
+#[cfg(not(feature = "consumer"))]
+fn consumer_release(_: u32) -> Option<WindowsRelease> {
+    None
+}
+
+#[cfg(feature = "enterprise")]
+fn enterprise_release(build: u32, editions: &[IsoEdition]) -> Option<WindowsRelease> {
+    use crate::EnterpriseRelease;
+    let is_server = editions
+        .iter()
+        .any(|e| e.installation_type.as_deref().is_some_and(|t| t.starts_with("Server")));
+    // LTSC editions are 'EnterpriseS', e.g. 'EnterpriseSEval'
+    let is_ltsc = editions
+        .iter()
+        .any(|e| e.edition_id.as_deref().is_some_and(|id| id.starts_with("EnterpriseS")));
+    let release = match (is_server, build) {
+        (true, 9600) => EnterpriseRelease::Server2012R2,
+        (true, 14393) => EnterpriseRelease::Server2016,
+        (true, 17763) => EnterpriseRelease::Server2019,
+        (true, 20348) => EnterpriseRelease::Server2022,
+        (true, _) => return None,
+        (false, 22000..) => EnterpriseRelease::ElevenEnterprise,
+        (false, _) if is_ltsc => EnterpriseRelease::TenLtsc,
+        (false, _) => EnterpriseRelease::TenEnterprise,
+    };
+    Some(release.into())
+}
+#[cfg(not(feature = "enterprise"))]
+fn enterprise_release(_: u32, _: &[IsoEdition]) -> Option<WindowsRelease> {
+    None
+}
+
+fn language_for_locale(release: WindowsRelease, locale: &str) -> Option<WindowsLanguage> {
+    match release {
+        #[cfg(feature = "consumer")]
+        WindowsRelease::Consumer(_) => {
+            use strum::IntoEnumIterator;
+            crate::ConsumerLanguage::iter()
+                .find(|lang| lang.locale().eq_ignore_ascii_case(locale))
+                .map(Into::into)
+        }
+        #[cfg(feature = "enterprise")]
+        WindowsRelease::Enterprise(_) => {
+            use strum::IntoEnumIterator;
+            crate::EnterpriseLanguage::iter()
+                .find(|lang| lang.locale().eq_ignore_ascii_case(locale))
+                .map(Into::into)
+        }
+    }
+}
+
+fn iso_error(message: &str) -> RidoError {
+    RidoError::Iso(message.to_string())
+}
+
+/// A contiguous run of a file's data on disk
+#[derive(Debug, Clone, Copy)]
+struct Extent {
+    offset: u64,
+    length: u64,
+}
+
+fn read_at(file: &mut File, offset: u64, buffer: &mut [u8]) -> Result<(), RidoError> {
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(buffer)?;
+    Ok(())
+}
+
+/// Read `length` bytes starting at `offset` within a file made up of the given extents
+fn read_extents(file: &mut File, extents: &[Extent], mut offset: u64, length: u64) -> Result<Vec<u8>, RidoError> {
+    // The length comes from the image, so don't trust it for an allocation before any data has been read
+    let mut data = Vec::with_capacity(length.min(MAX_DIRECTORY_SIZE) as usize);
+    for extent in extents {
+        if offset >= extent.length {
+            offset -= extent.length;
+            continue;
+        }
+        let wanted = (length - data.len() as u64).min(extent.length - offset);
+        let mut buffer = vec![0; wanted as usize];
+        read_at(file, extent.offset + offset, &mut buffer)?;
+        data.extend_from_slice(&buffer);
+        offset = 0;
+        if data.len() as u64 == length {
+            return Ok(data);
+        }
+    }
+    Err(iso_error("file data extends past its extents"))
+}
+
+fn total_length(extents: &[Extent]) -> u64 {
+    extents.iter().map(|extent| extent.length).sum()
+}
+
+/// Find a file by its path components, preferring UDF (which Windows ISOs rely on) and falling back to ISO9660
+fn find_file(file: &mut File, path: &[&str]) -> Result<Option<Vec<Extent>>, RidoError> {
+    if let Some(udf) = Udf::open(file)? {
+        return udf.find_file(file, path);
+    }
+    iso9660_find_file(file, path)
+}
+
+fn bytes_at<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N], RidoError> {
+    data.get(offset..offset + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| iso_error("truncated filesystem structure"))
+}
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, RidoError> {
+    bytes_at(data, offset).map(u16::from_le_bytes)
+}
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, RidoError> {
+    bytes_at(data, offset).map(u32::from_le_bytes)
+}
+fn u64_at(data: &[u8], offset: usize) -> Result<u64, RidoError> {
+    bytes_at(data, offset).map(u64::from_le_bytes)
+}
+
+fn iso9660_find_file(file: &mut File, path: &[&str]) -> Result<Option<Vec<Extent>>, RidoError> {
+    let mut descriptor = [0; SECTOR_SIZE as usize];
+    read_at(file, 16 * SECTOR_SIZE, &mut descriptor)?;
+    if descriptor[0] != 1 || &descriptor[1..6] != b"CD001" {
+        return Err(iso_error("neither a UDF nor an ISO9660 filesystem"));
+    }
+
+    // The root directory record is embedded in the primary volume descriptor
+    let mut directory = iso9660_record_extent(&descriptor[156..190])?;
+    for (depth, name) in path.iter().enumerate() {
+        if directory.length > MAX_DIRECTORY_SIZE {
+            return Err(iso_error("ISO9660 directory is too large"));
+        }
+        let data = read_extents(file, &[directory], 0, directory.length)?;
+        let mut offset = 0;
+        let mut found = None;
+        while offset < data.len() {
+            let record_length = data[offset] as usize;
+            if record_length == 0 {
+                // Records never span sectors; skip to the next one
+                offset = (offset / SECTOR_SIZE as usize + 1) * SECTOR_SIZE as usize;
+                continue;
+            }
+            // A record is at least 33 bytes followed by its name
+            let record = data
+                .get(offset..offset + record_length)
+                .filter(|record| record.len() > 33)
+                .ok_or_else(|| iso_error("truncated directory record"))?;
+            let name_length = record[32] as usize;
+            let record_name = String::from_utf8_lossy(record.get(33..33 + name_length).unwrap_or_default());
+            // Names are stored as e.g. 'INSTALL.WIM;1'
+            let record_name = record_name.split(';').next().unwrap_or_default().trim_end_matches('.');
+            let is_directory = record[25] & 0x02 != 0;
+            if record_name.eq_ignore_ascii_case(name) && is_directory == (depth + 1 < path.len()) {
+                found = Some(iso9660_record_extent(record)?);
+                break;
+            }
+            offset += record_length;
+        }
+        match found {
+            Some(extent) => directory = extent,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(vec![directory]))
+}
+
+fn iso9660_record_extent(record: &[u8]) -> Result<Extent, RidoError> {
+    Ok(Extent {
+        offset: u32_at(record, 2)? as u64 * SECTOR_SIZE,
+        length: u32_at(record, 10)? as u64,
+    })
+}
+
+struct Udf {
+    partition_start: u64,
+    block_size: u64,
+    root_icb: u32,
+}
+
+const TAG_ANCHOR: u16 = 2;
+const TAG_PARTITION: u16 = 5;
+const TAG_LOGICAL_VOLUME: u16 = 6;
+const TAG_TERMINATING: u16 = 8;
+const TAG_FILE_SET: u16 = 256;
+const TAG_FILE_IDENTIFIER: u16 = 257;
+const TAG_FILE_ENTRY: u16 = 261;
+const TAG_EXTENDED_FILE_ENTRY: u16 = 266;
+
+impl Udf {
+    fn open(file: &mut File) -> Result<Option<Self>, RidoError> {
+        // The anchor volume descriptor pointer is always at sector 256
+        let mut anchor = [0; SECTOR_SIZE as usize];
+        if read_at(file, 256 * SECTOR_SIZE, &mut anchor).is_err() || u16_at(&anchor, 0)? != TAG_ANCHOR {
+            return Ok(None);
+        }
+        let sequence_length = u32_at(&anchor, 16)? as u64;
+        let sequence_start = u32_at(&anchor, 20)? as u64;
+
+        let mut partition_start = None;
+        let mut logical_volume = None;
+        for sector in sequence_start..sequence_start + sequence_length / SECTOR_SIZE {
+            let mut descriptor = [0; SECTOR_SIZE as usize];
+            read_at(file, sector * SECTOR_SIZE, &mut descriptor)?;
+            match u16_at(&descriptor, 0)? {
+                TAG_PARTITION => partition_start = Some(u32_at(&descriptor, 188)? as u64),
+                TAG_LOGICAL_VOLUME => {
+                    // The logical volume contents use field holds the long_ad of the file set descriptor
+                    logical_volume = Some((u32_at(&descriptor, 212)? as u64, u32_at(&descriptor, 252)?));
+                }
+                TAG_TERMINATING => break,
+                _ => {}
+            }
+        }
+        let partition_start = partition_start.ok_or_else(|| iso_error("UDF partition descriptor not found"))?;
+        let (block_size, file_set) = logical_volume.ok_or_else(|| iso_error("UDF logical volume descriptor not found"))?;
+        // Descriptors are read whole into a single block, so it must be large enough to hold them
+        if !(512..=4096).contains(&block_size) || !block_size.is_power_of_two() {
+            return Err(iso_error("invalid UDF block size"));
+        }
+
+        let mut file_set_descriptor = vec![0; block_size as usize];
+        read_at(file, (partition_start + file_set as u64) * block_size, &mut file_set_descriptor)?;
+        if u16_at(&file_set_descriptor, 0)? != TAG_FILE_SET {
+            return Err(iso_error("UDF file set descriptor not found"));
+        }
+        let root_icb = u32_at(&file_set_descriptor, 404)?;
+        Ok(Some(Self {
+            partition_start,
+            block_size,
+            root_icb,
+        }))
+    }
+
+    fn find_file(&self, file: &mut File, path: &[&str]) -> Result<Option<Vec<Extent>>, RidoError> {
+        let mut icb = self.root_icb;
+        for name in path {
+            let directory = self.file_extents(file, icb)?;
+            if total_length(&directory) > MAX_DIRECTORY_SIZE {
+                return Err(iso_error("UDF directory is too large"));
+            }
+            let data = read_extents(file, &directory, 0, total_length(&directory))?;
+            match self.find_in_directory(&data, name)? {
+                Some(child) => icb = child,
+                None => return Ok(None),
+            }
+        }
+        self.file_extents(file, icb).map(Some)
+    }
+
+    fn find_in_directory(&self, data: &[u8], name: &str) -> Result<Option<u32>, RidoError> {
+        let mut offset = 0;
+        while offset + 38 <= data.len() {
+            let descriptor = &data[offset..];
+            if u16_at(descriptor, 0)? != TAG_FILE_IDENTIFIER {
+                return Err(iso_error("invalid UDF file identifier descriptor"));
+            }
+            let characteristics = descriptor[18];
+            let name_length = descriptor[19] as usize;
+            let icb = u32_at(descriptor, 24)?;
+            let implementation_use_length = u16_at(descriptor, 36)? as usize;
+            let name_start = 38 + implementation_use_length;
+            let identifier = descriptor
+                .get(name_start..name_start + name_length)
+                .ok_or_else(|| iso_error("truncated UDF file identifier"))?;
+
+            // Skip the parent directory entry
+            if characteristics & 0x08 == 0 && decode_udf_name(identifier).eq_ignore_ascii_case(name) {
+                return Ok(Some(icb));
+            }
+            // Descriptors are padded to a multiple of 4 bytes
+            offset += (name_start + name_length + 3) & !3;
+        }
+        Ok(None)
+    }
+
+    fn file_extents(&self, file: &mut File, icb: u32) -> Result<Vec<Extent>, RidoError> {
+        let icb_offset = (self.partition_start + icb as u64) * self.block_size;
+        let mut entry = vec![0; self.block_size as usize];
+        read_at(file, icb_offset, &mut entry)?;
+
+        let (extended_attributes_length, descriptors_length, header_length) = match u16_at(&entry, 0)? {
+            TAG_FILE_ENTRY => (u32_at(&entry, 168)?, u32_at(&entry, 172)?, 176),
+            TAG_EXTENDED_FILE_ENTRY => (u32_at(&entry, 208)?, u32_at(&entry, 212)?, 216),
+            _ => return Err(iso_error("invalid UDF file entry")),
+        };
+        let information_length = u64_at(&entry, 56)?;
+        let start = header_length + extended_attributes_length as usize;
+        let descriptors = start
+            .checked_add(descriptors_length as usize)
+            .and_then(|end| entry.get(start..end))
+            .ok_or_else(|| iso_error("truncated UDF allocation descriptors"))?;
+
+        // The allocation descriptor type is stored in the low bits of the ICB tag's flags
+        let ad_size = match u16_at(&entry, 34)? & 0x07 {
+            // Short allocation descriptors: length, then position within the partition
+            0 => 8,
+            // Long allocation descriptors: length, then a logical block address
+            1 => 16,
+            // The data is embedded in the file entry itself
+            3 => {
+                return Ok(vec![Extent {
+                    offset: icb_offset + start as u64,
+                    length: information_length.min(descriptors_length as u64),
+                }])
+            }
+            _ => return Err(iso_error("unsupported UDF allocation descriptor type")),
+        };
+        let mut extents = Vec::new();
+        for ad in descriptors.chunks_exact(ad_size) {
+            let (length, position) = (u32_at(ad, 0)?, u32_at(ad, 4)?);
+            // The top two bits of the length give the extent type; only recorded extents (0) hold data
+            if length >> 30 == 0 && length & 0x3FFF_FFFF != 0 {
+                extents.push(Extent {
+                    offset: (self.partition_start + position as u64) * self.block_size,
+                    length: (length & 0x3FFF_FFFF) as u64,
+                });
+            }
+        }
+        Ok(extents)
+    }
+}
+
+fn decode_udf_name(identifier: &[u8]) -> String {
+    match identifier.split_first() {
+        // Compression ID 8: one byte per character
+        Some((8, name)) => name.iter().map(|&b| b as char).collect(),
+        // Compression ID 16: UCS-2 big endian
+        Some((16, name)) => String::from_utf16_lossy(
+            &name
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect::<Vec<_>>(),
+        ),
+        _ => String::new(),
+    }
+}
+
+fn read_wim_xml(file: &mut File, extents: &[Extent]) -> Result<String, RidoError> {
+    let header = read_extents(file, extents, 0, 208)?;
+    if &header[..8] != b"MSWIM\0\0\0" {
+        return Err(iso_error("install image is not a WIM or ESD file"));
+    }
+    // The XML resource header: a 7 byte size with a flags byte, followed by its offset
+    let size = u64_at(&header, 0x48)? & 0x00FF_FFFF_FFFF_FFFF;
+    let offset = u64_at(&header, 0x50)?;
+    if !(2..=MAX_XML_SIZE).contains(&size) || offset.checked_add(size).is_none_or(|end| end > total_length(extents)) {
+        return Err(iso_error("invalid WIM XML resource"));
+    }
+    let data = read_extents(file, extents, offset, size)?;
+    // The XML is UTF-16LE with a byte order mark
+    let utf16 = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect::<Vec<_>>();
+    Ok(String::from_utf16_lossy(&utf16).trim_start_matches('\u{feff}').to_string())
+}
+
+fn child<'a, 'input>(node: roxmltree::Node<'a, 'input>, name: &str) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn text(node: Option<roxmltree::Node>) -> Option<String> {
+    node.and_then(|n| n.text()).map(|t| t.trim().to_string())
+}
+
+fn parse_wim_xml(xml: &str) -> Result<Vec<IsoEdition>, RidoError> {
+    let document = roxmltree::Document::parse(xml).map_err(|e| RidoError::Iso(format!("invalid WIM XML: {e}")))?;
+
+    Ok(document
+        .root_element()
+        .children()
+        .filter(|n| n.has_tag_name("IMAGE"))
+        .map(|image| {
+            let windows = child(image, "WINDOWS");
+            let version = windows.and_then(|w| child(w, "VERSION"));
+            let languages = windows.and_then(|w| child(w, "LANGUAGES"));
+            let default_language = text(languages.and_then(|l| child(l, "DEFAULT")));
+            // The default language is listed first
+            let mut language_list = languages
+                .into_iter()
+                .flat_map(|l| l.children().filter(|n| n.has_tag_name("LANGUAGE")))
+                .filter_map(|n| text(Some(n)))
+                .collect::<Vec<_>>();
+            if let Some(default) = default_language {
+                language_list.retain(|l| *l != default);
+                language_list.insert(0, default);
+            }
+
+            IsoEdition {
+                index: image.attribute("INDEX").and_then(|i| i.parse().ok()).unwrap_or_default(),
+                name: text(child(image, "NAME")).unwrap_or_default(),
+                edition_id: text(windows.and_then(|w| child(w, "EDITIONID"))),
+                installation_type: text(windows.and_then(|w| child(w, "INSTALLATIONTYPE"))),
+                // Architectures use the PROCESSOR_ARCHITECTURE values: 0 for x86, 9 for x64
+                arch: match text(windows.and_then(|w| child(w, "ARCH"))).as_deref() {
+                    Some("0") => Some(WindowsArchitecture::i686),
+                    Some("9") => Some(WindowsArchitecture::x86_64),
+                    _ => None,
+                },
+                languages: language_list,
+                build: text(version.and_then(|v| child(v, "BUILD"))).and_then(|b| b.parse().ok()),
+                revision: text(version.and_then(|v| child(v, "SPBUILD"))).and_then(|b| b.parse().ok()),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = "<WIM><TOTALBYTES>4905385530</TOTALBYTES>\
+        <IMAGE INDEX=\"1\"><NAME>Windows 11 Home</NAME><WINDOWS><ARCH>9</ARCH><EDITIONID>Core</EDITIONID>\
+        <INSTALLATIONTYPE>Client</INSTALLATIONTYPE><LANGUAGES><LANGUAGE>en-US</LANGUAGE><DEFAULT>en-US</DEFAULT></LANGUAGES>\
+        <VERSION><MAJOR>10</MAJOR><MINOR>0</MINOR><BUILD>26100</BUILD><SPBUILD>1742</SPBUILD></VERSION></WINDOWS></IMAGE>\
+        <IMAGE INDEX=\"2\"><NAME>Windows 11 Pro</NAME><WINDOWS><ARCH>9</ARCH><EDITIONID>Professional</EDITIONID>\
+        <INSTALLATIONTYPE>Client</INSTALLATIONTYPE><LANGUAGES><LANGUAGE>en-US</LANGUAGE><DEFAULT>en-US</DEFAULT></LANGUAGES>\
+        <VERSION><MAJOR>10</MAJOR><MINOR>0</MINOR><BUILD>26100</BUILD><SPBUILD>1742</SPBUILD></VERSION></WINDOWS></IMAGE></WIM>";
+
+    // The UDF test image's partition starts after the anchor volume descriptor pointer
+    const PARTITION: u64 = 260;
+
+    /// A WIM header followed directly by its UTF-16LE XML resource
+    fn wim() -> Vec<u8> {
+        let xml = "\u{feff}"
+            .chars()
+            .chain(XML.chars())
+            .collect::<String>()
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+        let mut wim = vec![0; 208];
+        wim[..8].copy_from_slice(b"MSWIM\0\0\0");
+        put(&mut wim, 0x48, &(xml.len() as u64).to_le_bytes());
+        put(&mut wim, 0x50, &208u64.to_le_bytes());
+        wim.extend(xml);
+        wim
+    }
+
+    fn put(image: &mut [u8], offset: usize, bytes: &[u8]) {
+        image[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn sector(image: &mut Vec<u8>, index: u64) -> &mut [u8] {
+        let start = (index * SECTOR_SIZE) as usize;
+        if image.len() < start + SECTOR_SIZE as usize {
+            image.resize(start + SECTOR_SIZE as usize, 0);
+        }
+        &mut image[start..start + SECTOR_SIZE as usize]
+    }
+
+    fn iso9660_record(name: &[u8], lba: u32, length: u32, directory: bool) -> Vec<u8> {
+        let mut record = vec![0; (33 + name.len() + 1) & !1];
+        record[0] = record.len() as u8;
+        put(&mut record, 2, &lba.to_le_bytes());
+        put(&mut record, 10, &length.to_le_bytes());
+        record[25] = if directory { 0x02 } else { 0 };
+        record[32] = name.len() as u8;
+        put(&mut record, 33, name);
+        record
+    }
+
+    /// An ISO9660 filesystem with no UDF descriptors: root at 18, SOURCES at 19 and INSTALL.WIM from 20
+    fn iso9660_image() -> Vec<u8> {
+        let wim = wim();
+        let mut image = Vec::new();
+        let descriptor = sector(&mut image, 16);
+        descriptor[0] = 1;
+        put(descriptor, 1, b"CD001");
+        put(descriptor, 156, &iso9660_record(&[0], 18, 2048, true));
+        put(sector(&mut image, 18), 0, &iso9660_record(b"SOURCES", 19, 2048, true));
+        let sources = [iso9660_record(b"BOOT.WIM;1", 30, 0, false), iso9660_record(b"INSTALL.WIM;1", 20, wim.len() as u32, false)].concat();
+        put(sector(&mut image, 19), 0, &sources);
+        image.resize(20 * SECTOR_SIZE as usize, 0);
+        image.extend(wim);
+        image
+    }
+
+    fn udf_file_identifier(name: &str, icb: u32, characteristics: u8) -> Vec<u8> {
+        let name = if name.is_empty() { Vec::new() } else { [&[8], name.as_bytes()].concat() };
+        let mut descriptor = vec![0; (38 + name.len() + 3) & !3];
+        put(&mut descriptor, 0, &TAG_FILE_IDENTIFIER.to_le_bytes());
+        descriptor[18] = characteristics;
+        descriptor[19] = name.len() as u8;
+        put(&mut descriptor, 24, &icb.to_le_bytes());
+        put(&mut descriptor, 38, &name);
+        descriptor
+    }
+
+    /// A file entry with a single short allocation descriptor
+    fn udf_file_entry(length: u32, block: u32) -> Vec<u8> {
+        let mut entry = vec![0; 184];
+        put(&mut entry, 0, &TAG_FILE_ENTRY.to_le_bytes());
+        put(&mut entry, 56, &(length as u64).to_le_bytes());
+        put(&mut entry, 172, &8u32.to_le_bytes());
+        put(&mut entry, 176, &length.to_le_bytes());
+        put(&mut entry, 180, &block.to_le_bytes());
+        entry
+    }
+
+    /// A UDF filesystem whose blocks are always laid out as 2048 bytes, whatever block size is recorded
+    fn udf_image(block_size: u32) -> Vec<u8> {
+        let wim = wim();
+        let mut image = Vec::new();
+
+        let anchor = sector(&mut image, 256);
+        put(anchor, 0, &TAG_ANCHOR.to_le_bytes());
+        put(anchor, 16, &(3 * SECTOR_SIZE as u32).to_le_bytes());
+        put(anchor, 20, &32u32.to_le_bytes());
+        let partition = sector(&mut image, 32);
+        put(partition, 0, &TAG_PARTITION.to_le_bytes());
+        put(partition, 188, &(PARTITION as u32).to_le_bytes());
+        let logical_volume = sector(&mut image, 33);
+        put(logical_volume, 0, &TAG_LOGICAL_VOLUME.to_le_bytes());
+        put(logical_volume, 212, &block_size.to_le_bytes());
+        put(logical_volume, 252, &0u32.to_le_bytes());
+        put(sector(&mut image, 34), 0, &TAG_TERMINATING.to_le_bytes());
+
+        let file_set = sector(&mut image, PARTITION);
+        put(file_set, 0, &TAG_FILE_SET.to_le_bytes());
+        put(file_set, 404, &1u32.to_le_bytes());
+
+        let root = [udf_file_identifier("", 1, 0x0A), udf_file_identifier("sources", 3, 0x02)].concat();
+        put(sector(&mut image, PARTITION + 1), 0, &udf_file_entry(root.len() as u32, 2));
+        put(sector(&mut image, PARTITION + 2), 0, &root);
+        let sources = [udf_file_identifier("", 1, 0x0A), udf_file_identifier("boot.wim", 7, 0), udf_file_identifier("install.wim", 5, 0)].concat();
+        put(sector(&mut image, PARTITION + 3), 0, &udf_file_entry(sources.len() as u32, 4));
+        put(sector(&mut image, PARTITION + 4), 0, &sources);
+        put(sector(&mut image, PARTITION + 5), 0, &udf_file_entry(wim.len() as u32, 6));
+        image.resize(((PARTITION + 6) * SECTOR_SIZE) as usize, 0);
+        image.extend(wim);
+        image.resize(image.len().next_multiple_of(SECTOR_SIZE as usize), 0);
+        image
+    }
+
+    fn identify(name: &str, image: &[u8]) -> Result<IsoIdentity, RidoError> {
+        let path = std::env::temp_dir().join(format!("rido-{name}-{}.iso", std::process::id()));
+        std::fs::write(&path, image).unwrap();
+        let identity = IsoIdentity::from_path(&path);
+        std::fs::remove_file(path).unwrap();
+        identity
+    }
+
+    fn assert_windows_11(identity: IsoIdentity) {
+        assert_eq!(
+            identity.entry,
+            WindowsEntry::new("11", "English (United States)", "x86_64").ok()
+        );
+        assert_eq!((identity.version.build, identity.version.revision), (Some(26100), Some(1742)));
+        let names = identity
+            .editions
+            .iter()
+            .map(|edition| edition.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Windows 11 Home", "Windows 11 Pro"]);
+    }
+
+    #[test]
+    fn identify_udf() {
+        assert_windows_11(identify("udf", &udf_image(2048)).unwrap());
+    }
+
+    #[test]
+    fn identify_iso9660() {
+        assert_windows_11(identify("iso9660", &iso9660_image()).unwrap());
+    }
+
+    #[test]
+    fn malformed_images_are_rejected() {
+        assert!(matches!(identify("block-size", &udf_image(4)), Err(RidoError::Iso(_))));
+        assert!(matches!(identify("block-size-odd", &udf_image(3000)), Err(RidoError::Iso(_))));
+
+        let mut image = udf_image(2048);
+        image.truncate(((PARTITION + 2) * SECTOR_SIZE) as usize);
+        assert!(identify("truncated", &image).is_err());
+
+        // A directory record too short to hold its own name
+        let mut image = iso9660_image();
+        image[19 * SECTOR_SIZE as usize] = 20;
+        assert!(matches!(identify("short-record", &image), Err(RidoError::Iso(_))));
+
+        assert!(matches!(identify("empty", &[0; 40000]), Err(RidoError::Iso(_))));
+    }
+
+    #[test]
+    fn wim_xml() {
+        let editions = parse_wim_xml(XML).unwrap();
+        assert_eq!(
+            editions[1],
+            IsoEdition {
+                index: 2,
+                name: "Windows 11 Pro".to_string(),
+                edition_id: Some("Professional".to_string()),
+                installation_type: Some("Client".to_string()),
+                arch: Some(WindowsArchitecture::x86_64),
+                languages: vec!["en-US".to_string()],
+                build: Some(26100),
+                revision: Some(1742),
+            }
+        );
+        assert!(parse_wim_xml("<WIM>").is_err());
+    }
+}
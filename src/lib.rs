@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{fmt, str::FromStr};
 use strum::IntoEnumIterator;
 use strum_macros::{Display, EnumIter};
 use thiserror::Error;
@@ -6,18 +6,63 @@ use thiserror::Error;
 #[cfg(feature = "consumer")]
 mod consumer;
 #[cfg(feature = "consumer")]
-pub use consumer::{ConsumerLanguage, ConsumerRelease};
+pub use consumer::{ConsumerLanguage, ConsumerProvider, ConsumerRelease, ProductInfo};
 
 #[cfg(feature = "enterprise")]
 mod enterprise;
 #[cfg(feature = "enterprise")]
-pub use enterprise::{EnterpriseLanguage, EnterpriseRelease};
+pub use enterprise::{EnterpriseLanguage, EnterpriseProvider, EnterpriseRelease};
+
+mod provider;
+pub use provider::{Provider, ProviderCapabilities, ProviderRegistry, ResolvedImage};
+
+mod catalog;
+mod checksum;
+pub use catalog::{Catalog, CatalogChange, CatalogDiff, CatalogFailure, CatalogImage, CatalogKey};
+pub use checksum::sha256_file;
+
+mod download;
+pub use download::Downloader;
+
+pub mod export;
+
+mod iso;
+pub use iso::{IsoEdition, IsoIdentity};
+
+mod mirror;
+pub use mirror::{MirrorLocation, MirrorProvider};
+
+mod query;
+pub use query::{EntryQuery, ReleaseFamily};
+
+mod remote;
+pub use remote::{BuildDate, ImageBuild, ImageProbe, RemoteMetadata};
+
+#[cfg(feature = "server")]
+mod server;
+#[cfg(feature = "server")]
+pub use server::{Server, ServerOptions};
+
+pub mod unattend;
+
+mod version;
+pub use version::WindowsVersion;
+
+pub mod vm;
+
+mod watch;
+pub use watch::Watcher;
 
 #[derive(Debug, Clone)]
 pub struct WindowsData {
     pub info: WindowsEntry,
     pub url: String,
     pub hash: Option<String>,
+    /// Size of the image in bytes, if known
+    pub size: Option<u64>,
+    pub version: WindowsVersion,
+    /// Populated by `resolve_remote`
+    pub remote: Option<RemoteMetadata>,
 }
 
 impl WindowsData {
@@ -27,14 +72,66 @@ impl WindowsData {
         R: TryInto<WindowsRelease, Error = RidoError>,
         A: TryInto<WindowsArchitecture, Error = RidoError>,
     {
-        let release = release.try_into()?;
-        let lang = (release, lang).try_into()?;
-        let arch = arch.try_into()?;
-        WindowsEntry { release, lang, arch }.try_into()
+        WindowsEntry::new(release, lang, arch)?.try_into()
+    }
+
+    /// Follow the redirect chain of the download URL (e.g. go.microsoft.com/fwlink links) and record
+    /// the final URL along with the file's metadata, using a HEAD request.
+    pub fn resolve_remote(&mut self) -> Result<&RemoteMetadata, RidoError> {
+        let client = reqwest::blocking::Client::new();
+        let metadata = remote::fetch_metadata(&client, &self.url)?;
+        if let Some(filename) = &metadata.filename {
+            self.version.merge(WindowsVersion::from_text(filename));
+        }
+        self.size = self.size.or(metadata.content_length);
+        Ok(self.remote.insert(metadata))
+    }
+
+    /// The URL to download the image from, which is the end of the redirect chain if it has been resolved
+    pub fn download_url(&self) -> &str {
+        self.remote.as_ref().map_or(&self.url, |remote| &remote.final_url)
+    }
+
+    /// The image's filename, as reported by the server if known. Otherwise, it's taken from the URL,
+    /// or generated from the entry if the URL doesn't point directly to an ISO.
+    pub fn filename(&self) -> String {
+        if let Some(filename) = self.remote.as_ref().and_then(|remote| remote.filename.clone()) {
+            return filename;
+        }
+        reqwest::Url::parse(&self.url)
+            .ok()
+            .and_then(|url| url.path_segments()?.next_back().map(str::to_string))
+            .filter(|name| name.to_ascii_lowercase().ends_with(".iso"))
+            .unwrap_or_else(|| {
+                let name = format!("{} {} {}", self.info.release, self.info.lang, self.info.arch);
+                let name = name
+                    .split(|c: char| !c.is_ascii_alphanumeric())
+                    .filter(|part| !part.is_empty())
+                    .collect::<Vec<_>>();
+                format!("{}.iso", name.join("_"))
+            })
+    }
+
+    /// Check a local file against the image's hash, returning whether it matches
+    pub fn verify_file(&self, path: impl AsRef<std::path::Path>) -> Result<bool, RidoError> {
+        let expected = self.hash.as_ref().ok_or(RidoError::NoHash)?;
+        Ok(sha256_file(path.as_ref())?.eq_ignore_ascii_case(expected))
+    }
+
+    /// Download the image to `path` over several connections at once. See [`Downloader`] for more options.
+    pub fn download(&self, path: impl AsRef<std::path::Path>) -> Result<(), RidoError> {
+        Downloader::new().download(self, path.as_ref())
+    }
+
+    /// Determine the image's size, filename and build without downloading it, using a HEAD request
+    /// or a ranged GET if the server doesn't report a size in response to HEAD.
+    pub fn probe(&self) -> Result<ImageProbe, RidoError> {
+        let client = reqwest::blocking::Client::new();
+        remote::probe(&client, &self.url)
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
 pub struct WindowsEntry {
     pub release: WindowsRelease,
     pub arch: WindowsArchitecture,
@@ -42,71 +139,257 @@ pub struct WindowsEntry {
 }
 
 impl WindowsEntry {
+    const DEFAULT_LANGUAGE: &'static str = "English (United States)";
+
+    /// Create an entry, checking that the language and architecture are available for the release
+    pub fn new<R, L, A>(release: R, lang: L, arch: A) -> Result<Self, RidoError>
+    where
+        WindowsLanguage: TryFrom<(WindowsRelease, L), Error = RidoError>,
+        R: TryInto<WindowsRelease, Error = RidoError>,
+        A: TryInto<WindowsArchitecture, Error = RidoError>,
+    {
+        let release = release.try_into()?;
+        let lang = (release, lang).try_into()?;
+        let arch = arch.try_into()?;
+        let entry = Self { release, lang, arch };
+        entry.validate()?;
+        Ok(entry)
+    }
+
+    /// Build an entry, which defaults to English (United States) and x86_64
+    pub fn builder(release: impl Into<WindowsRelease>) -> WindowsEntryBuilder {
+        WindowsEntryBuilder {
+            release: release.into(),
+            lang: None,
+            arch: WindowsArchitecture::x86_64,
+        }
+    }
+
     pub fn list_all() -> Vec<Self> {
-        let mut entries = Vec::new();
+        ProviderRegistry::default().list_all()
+    }
 
-        #[cfg(feature = "consumer")]
-        entries.extend(ConsumerRelease::iter().flat_map(move |rel| {
-            let release: WindowsRelease = rel.into();
-            ConsumerLanguage::iter()
-                .filter(move |&lang| lang.validate(release))
-                .flat_map(move |lang| {
-                    let lang = lang.into();
-                    WindowsArchitecture::iter()
-                        .filter(move |&arch| rel.validate(arch))
-                        .map(move |arch| Self { release, lang, arch })
-                })
-        }));
+    /// Filter the available entries by family, language, architecture or release. See [`EntryQuery`].
+    pub fn query() -> EntryQuery {
+        EntryQuery::new()
+    }
 
-        #[cfg(feature = "enterprise")]
-        entries.extend(EnterpriseRelease::iter().flat_map(move |rel| {
-            let release: WindowsRelease = rel.into();
-            EnterpriseLanguage::iter()
-                .filter(move |&lang| lang.validate(release))
-                .flat_map(move |lang| {
-                    let lang = lang.into();
-                    WindowsArchitecture::iter()
-                        .filter(move |&arch| rel.validate(arch))
-                        .map(move |arch| Self { release, lang, arch })
-                })
-        }));
+    /// Check that the language and architecture are available for the release
+    pub fn validate(&self) -> Result<(), RidoError> {
+        let languages = self.release.languages();
+        if !languages.contains(&self.lang) {
+            return Err(RidoError::InvalidLanguage {
+                release: self.release,
+                language: self.lang.to_string(),
+                allowed: languages.iter().map(ToString::to_string).collect(),
+            });
+        }
+        let allowed = self.release.architectures();
+        if !allowed.contains(&self.arch) {
+            return Err(RidoError::InvalidArchitecture {
+                release: self.release,
+                arch: self.arch,
+                allowed,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The canonical spec, e.g. `enterprise/server-2022@de-de/x86_64`, which [`FromStr`] parses back into the same entry
+impl fmt::Display for WindowsEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let locale = self.lang.locale().to_ascii_lowercase();
+        write!(f, "{}/{}@{locale}/{}", self.release.source(), self.release.id(), self.arch)
+    }
+}
 
-        entries
+/// Parses either the canonical `[source/]release@language[/arch]` spec, e.g. `enterprise/server-2022@de-de/x86_64` or
+/// `productid:3113@English (United States)/x64`, or a compact `release:language:arch` spec, e.g. `11:en-US:x86_64`.
+/// In both, the language may be a name or locale tag, and the language and architecture may be omitted or left empty.
+impl FromStr for WindowsEntry {
+    type Err = RidoError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((release, rest)) = s.split_once('@') {
+            let release = match release.split_once('/') {
+                Some((source, release)) => {
+                    let release = WindowsRelease::try_from(release)?;
+                    if release.source() != source {
+                        return Err(RidoError::InvalidReleaseStr);
+                    }
+                    release
+                }
+                None => WindowsRelease::try_from(release)?,
+            };
+            // Language names never contain a slash, so one separates the architecture
+            let (lang, arch) = rest.rsplit_once('/').unwrap_or((rest, ""));
+            let lang = if lang.is_empty() { Self::DEFAULT_LANGUAGE } else { lang };
+            let arch = if arch.is_empty() { "x86_64" } else { arch };
+            let entry = Self {
+                release,
+                lang: (release, lang).try_into()?,
+                arch: arch.try_into()?,
+            };
+            entry.validate()?;
+            return Ok(entry);
+        }
+
+        // A release on its own, which may contain a colon, e.g. 'Custom Product ID: 3113'
+        if let Ok(release) = WindowsRelease::try_from(s) {
+            return WindowsEntry::builder(release).build();
+        }
+        // Product IDs contain a colon of their own
+        let (release, rest) = match s.strip_prefix("productid:") {
+            Some(rest) => {
+                let (id, rest) = rest.split_once(':').unwrap_or((rest, ""));
+                (format!("productid:{id}"), rest)
+            }
+            None => {
+                let (release, rest) = s.split_once(':').unwrap_or((s, ""));
+                (release.to_string(), rest)
+            }
+        };
+        let (lang, arch) = rest.split_once(':').unwrap_or((rest, ""));
+        let lang = if lang.is_empty() { Self::DEFAULT_LANGUAGE } else { lang };
+        let arch = if arch.is_empty() { "x86_64" } else { arch };
+        Self::new(release.as_str(), lang, arch)
+    }
+}
+
+impl serde::Serialize for WindowsEntry {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+impl<'de> serde::Deserialize<'de> for WindowsEntry {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let spec = String::deserialize(deserializer)?;
+        spec.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+pub struct WindowsEntryBuilder {
+    release: WindowsRelease,
+    lang: Option<WindowsLanguage>,
+    arch: WindowsArchitecture,
+}
+
+impl WindowsEntryBuilder {
+    pub fn lang(mut self, lang: impl Into<WindowsLanguage>) -> Self {
+        self.lang = Some(lang.into());
+        self
+    }
+    pub fn arch(mut self, arch: WindowsArchitecture) -> Self {
+        self.arch = arch;
+        self
+    }
+    pub fn build(self) -> Result<WindowsEntry, RidoError> {
+        let lang = match self.lang {
+            Some(lang) => lang,
+            None => (self.release, WindowsEntry::DEFAULT_LANGUAGE).try_into()?,
+        };
+        let entry = WindowsEntry {
+            release: self.release,
+            lang,
+            arch: self.arch,
+        };
+        entry.validate()?;
+        Ok(entry)
     }
 }
 
 impl TryFrom<WindowsEntry> for WindowsData {
     type Error = RidoError;
     fn try_from(entry: WindowsEntry) -> Result<Self, Self::Error> {
-        let (url, hash) = match (entry.release, entry.lang) {
-            #[cfg(feature = "consumer")]
-            (WindowsRelease::Consumer(release), WindowsLanguage::Consumer(lang)) => consumer::get_consumer_info(release, lang, entry.arch)?,
-            #[cfg(feature = "enterprise")]
-            (WindowsRelease::Enterprise(release), WindowsLanguage::Enterprise(lang)) => enterprise::get_enterprise_info(release, lang, entry.arch)?,
-            #[allow(unreachable_patterns)]
-            _ => return Err(RidoError::InvalidSelection),
-        };
-        Ok(Self { info: entry, url, hash })
+        ProviderRegistry::default().resolve(entry)
     }
 }
 
-#[derive(PartialEq, Debug, Copy, Clone)]
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
 pub enum WindowsRelease {
     #[cfg(feature = "consumer")]
     Consumer(ConsumerRelease),
     #[cfg(feature = "enterprise")]
     Enterprise(EnterpriseRelease),
 }
-impl fmt::Display for WindowsRelease {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl WindowsRelease {
+    /// The release's identifier, as accepted by `TryFrom<&str>`, e.g. 'server-2022'
+    pub fn id(&self) -> String {
+        match self {
+            #[cfg(feature = "consumer")]
+            Self::Consumer(release) => release.id(),
+            #[cfg(feature = "enterprise")]
+            Self::Enterprise(release) => release.id().to_string(),
+        }
+    }
+    /// The release's human-readable name, e.g. 'Windows Server 2022'
+    pub fn display_name(&self) -> String {
+        match self {
+            #[cfg(feature = "consumer")]
+            Self::Consumer(release) => release.display_name(),
+            #[cfg(feature = "enterprise")]
+            Self::Enterprise(release) => release.display_name().to_string(),
+        }
+    }
+    pub fn family(&self) -> ReleaseFamily {
+        match self {
+            #[cfg(feature = "consumer")]
+            Self::Consumer(_) => ReleaseFamily::Consumer,
+            #[cfg(feature = "enterprise")]
+            Self::Enterprise(release) if release.is_server() => ReleaseFamily::Server,
+            #[cfg(feature = "enterprise")]
+            Self::Enterprise(_) => ReleaseFamily::Enterprise,
+        }
+    }
+    /// The build number the release first shipped with, which orders releases across families.
+    /// Unknown for custom product IDs.
+    pub fn base_build(&self) -> Option<u32> {
+        match self {
+            #[cfg(feature = "consumer")]
+            Self::Consumer(release) => release.base_build(),
+            #[cfg(feature = "enterprise")]
+            Self::Enterprise(release) => Some(release.base_build()),
+        }
+    }
+    /// 'consumer' or 'enterprise'
+    fn source(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "consumer")]
+            Self::Consumer(_) => "consumer",
+            #[cfg(feature = "enterprise")]
+            Self::Enterprise(_) => "enterprise",
+        }
+    }
+    /// Every language the release is available in
+    pub fn languages(&self) -> Vec<WindowsLanguage> {
         match self {
             #[cfg(feature = "consumer")]
-            Self::Consumer(release) => write!(f, "{release}"),
+            Self::Consumer(_) => ConsumerLanguage::iter()
+                .filter(|lang| lang.validate(*self))
+                .map(Into::into)
+                .collect(),
             #[cfg(feature = "enterprise")]
-            Self::Enterprise(release) => write!(f, "{release}"),
+            Self::Enterprise(_) => EnterpriseLanguage::iter()
+                .filter(|lang| lang.validate(*self))
+                .map(Into::into)
+                .collect(),
+        }
+    }
+    /// Every architecture the release is available for
+    pub fn architectures(&self) -> Vec<WindowsArchitecture> {
+        match self {
+            #[cfg(feature = "consumer")]
+            Self::Consumer(release) => WindowsArchitecture::iter().filter(|&arch| release.validate(arch)).collect(),
+            #[cfg(feature = "enterprise")]
+            Self::Enterprise(release) => WindowsArchitecture::iter().filter(|&arch| release.validate(arch)).collect(),
         }
     }
 }
+impl fmt::Display for WindowsRelease {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.display_name())
+    }
+}
 impl TryFrom<&str> for WindowsRelease {
     type Error = RidoError;
     fn try_from(value: &str) -> Result<Self, Self::Error> {
@@ -122,13 +405,24 @@ impl TryFrom<&str> for WindowsRelease {
     }
 }
 
-#[derive(PartialEq, Debug, Copy, Clone)]
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
 pub enum WindowsLanguage {
     #[cfg(feature = "consumer")]
     Consumer(ConsumerLanguage),
     #[cfg(feature = "enterprise")]
     Enterprise(EnterpriseLanguage),
 }
+impl WindowsLanguage {
+    /// The language's locale tag, as used by Windows setup, e.g. 'en-US'
+    pub fn locale(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "consumer")]
+            Self::Consumer(lang) => lang.locale(),
+            #[cfg(feature = "enterprise")]
+            Self::Enterprise(lang) => lang.locale(),
+        }
+    }
+}
 impl fmt::Display for WindowsLanguage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -142,12 +436,27 @@ impl fmt::Display for WindowsLanguage {
 impl TryFrom<(WindowsRelease, &str)> for WindowsLanguage {
     type Error = RidoError;
     fn try_from(value: (WindowsRelease, &str)) -> Result<Self, Self::Error> {
-        match value.0 {
+        let (release, lang) = value;
+        let parsed: Result<WindowsLanguage, RidoError> = match release {
             #[cfg(feature = "consumer")]
-            WindowsRelease::Consumer(_) => ConsumerLanguage::try_from(value.1).map(Into::into),
+            WindowsRelease::Consumer(_) => ConsumerLanguage::try_from(lang).map(Into::into),
             #[cfg(feature = "enterprise")]
-            WindowsRelease::Enterprise(_) => EnterpriseLanguage::try_from(value.1).map(Into::into),
-        }
+            WindowsRelease::Enterprise(_) => EnterpriseLanguage::try_from(lang).map(Into::into),
+        };
+        // Fall back to locale tags, such as 'en-US'
+        let parsed = parsed.or_else(|e| {
+            release
+                .languages()
+                .into_iter()
+                .find(|l| l.locale().eq_ignore_ascii_case(lang))
+                .ok_or(e)
+        });
+        // A name which isn't recognised for this type of release gets the same error as one which the release isn't offered in
+        parsed.map_err(|_| RidoError::InvalidLanguage {
+            release,
+            language: value.1.to_string(),
+            allowed: release.languages().iter().map(ToString::to_string).collect(),
+        })
     }
 }
 impl<L> TryFrom<(WindowsRelease, L)> for WindowsLanguage
@@ -161,7 +470,7 @@ where
 }
 
 #[allow(non_camel_case_types)]
-#[derive(EnumIter, Debug, Display, Copy, Clone, PartialEq)]
+#[derive(EnumIter, Debug, Display, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum WindowsArchitecture {
     x86_64,
     i686,
@@ -179,10 +488,14 @@ impl TryFrom<&str> for WindowsArchitecture {
 
 #[derive(Debug, Error)]
 pub enum RidoError {
-    #[error("Specified architecture {1} is not available for release {0}")]
-    InvalidArchitecture(WindowsRelease, WindowsArchitecture),
-    #[error("Specified language {1} is not available for release {0}")]
-    InvalidLanguage(WindowsRelease, WindowsLanguage),
+    #[error("Specified architecture {arch} is not available for release {release}. Available architectures: {}", list(allowed))]
+    InvalidArchitecture {
+        release: WindowsRelease,
+        arch: WindowsArchitecture,
+        allowed: Vec<WindowsArchitecture>,
+    },
+    #[error("Specified language {language} is not available for release {release}. Available languages: {}", list(allowed))]
+    InvalidLanguage { release: WindowsRelease, language: String, allowed: Vec<String> },
     #[error("Invalid release")]
     InvalidReleaseStr,
     #[error("Invalid language")]
@@ -191,27 +504,113 @@ pub enum RidoError {
     InvalidArchitectureStr,
     #[error("The language type must match the release type (Enterprise/Consumer)")]
     InvalidSelection,
+    #[error("The base build of {0} is not known, so it can't be compared against other releases")]
+    UnknownBuild(WindowsRelease),
     #[error("Microsoft servers gave us an empty response to our request for an automated download.")]
     EmptyResponse,
     #[error("Microsoft blocked the automated download request based on your IP address.")]
     BlockedRequest,
     #[error("Unable to parse download link from HTML")]
     HTMLParse,
+    #[error("Could not find a download section for {0}")]
+    MissingSection(String),
+    #[error("Found multiple download sections matching {0}")]
+    AmbiguousSection(String),
     #[error("Could not parse JSON: {0}")]
     JSONParsing(serde_json::Error),
     #[error("Could not find SKUID")]
     SKUID,
     #[error("Could not find Product ID")]
     ProductID,
+    #[error("Microsoft no longer serves product ID {0}")]
+    ProductIdUnavailable(u32),
+    #[error("Product ID {0} ({1}) does not belong to a known Windows release")]
+    UnknownProduct(u32, String),
     #[error("Could not find URL")]
     URL,
+    #[error("No provider is able to resolve {0} ({1}, {2})")]
+    NoProvider(WindowsRelease, WindowsLanguage, WindowsArchitecture),
+    #[error("Provider error: {0}")]
+    Provider(Box<dyn std::error::Error + Send + Sync>),
+    #[error("Invalid mirror manifest: {0}")]
+    MirrorManifest(String),
+    #[error("Image {0} is listed in the mirror manifest but does not exist")]
+    MirrorMissing(std::path::PathBuf),
+    #[error("Watch hook failed: {0}")]
+    Hook(String),
+    #[error("The server did not report the size of the image")]
+    UnknownSize,
+    #[error("The server does not support ranged requests")]
+    RangesUnsupported,
+    #[error("Could not read ISO: {0}")]
+    Iso(String),
+    #[error("No checksum is known for this image")]
+    NoHash,
+    #[error("Checksum mismatch: expected {expected}, got {actual}")]
+    HashMismatch { expected: String, actual: String },
+    #[error("Too many requests; try again later")]
+    RateLimited,
+    #[cfg(feature = "server")]
+    #[error("Server error: {0}")]
+    Server(Box<dyn std::error::Error + Send + Sync>),
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
     #[error("Reqwest error: {0}")]
     Reqwest(#[from] reqwest::Error),
 }
 
+fn list<T: fmt::Display>(items: &[T]) -> String {
+    items.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+}
+
 pub(crate) trait ValidateLanguage {
     fn validate(&self, release: WindowsRelease) -> bool;
 }
 pub(crate) trait ValidateWithArch {
     fn validate(&self, arch: WindowsArchitecture) -> bool;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_round_trip() {
+        let entries = WindowsEntry::list_all();
+        assert!(!entries.is_empty());
+        for entry in entries {
+            let spec = entry.to_string();
+            assert_eq!(spec.parse::<WindowsEntry>().ok(), Some(entry.clone()), "{spec}");
+            // An empty architecture defaults to x86_64, like an empty language defaults to English
+            if let Some(spec) = spec.strip_suffix("x86_64") {
+                assert_eq!(spec.parse::<WindowsEntry>().ok(), Some(entry.clone()), "{spec}");
+            }
+            let json = serde_json::to_string(&entry).unwrap();
+            assert_eq!(serde_json::from_str::<WindowsEntry>(&json).unwrap(), entry, "{json}");
+        }
+    }
+
+    #[test]
+    fn empty_segments_use_defaults() {
+        let entry = WindowsEntry::list_all()
+            .into_iter()
+            .find(|entry| entry.lang.to_string() == WindowsEntry::DEFAULT_LANGUAGE && entry.arch == WindowsArchitecture::x86_64)
+            .unwrap();
+        let release = entry.release.id();
+        for spec in [
+            format!("{release}@{}/", WindowsEntry::DEFAULT_LANGUAGE),
+            format!("{release}@/"),
+            format!("{release}@/x86_64"),
+            format!("{release}@"),
+        ] {
+            assert_eq!(spec.parse::<WindowsEntry>().ok(), Some(entry.clone()), "{spec}");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "consumer")]
+    fn custom_product_id_round_trip() {
+        let entry = WindowsEntry::new("productid:3113", "English (United States)", "x86_64").unwrap();
+        assert_eq!(entry.to_string().parse::<WindowsEntry>().unwrap(), entry);
+    }
+}
@@ -1,11 +1,33 @@
 const LICENSE: &str = include_str!("../LICENSE");
 
 fn main() -> Result<(), rido::RidoError> {
-    let args: Vec<String> = std::env::args().collect();
-    let (release, language, arch) = match args.len() {
-        2 => (args[1].as_str(), "English (United States)", "x86_64"),
-        3 => (args[1].as_str(), args[2].as_str(), "x86_64"),
-        4 => (args[1].as_str(), args[2].as_str(), args[3].as_str()),
+    let mut args: Vec<String> = std::env::args().collect();
+    // A mirror takes precedence over the built-in providers for every command
+    let mirror = args.iter().position(|arg| arg == "--mirror").map(|index| {
+        let location = args.get(index + 1).cloned().unwrap_or_else(|| invalid_argument("--mirror"));
+        args.drain(index..index + 2);
+        location
+    });
+    let registry = || registry(mirror.as_deref());
+    match args.get(1).map(String::as_str) {
+        Some("list") => return list(registry()?, &args[2..]),
+        Some("catalog") => return catalog(registry()?, args.get(2).map(String::as_str) == Some("--remote")),
+        Some("diff") if args.len() == 4 => return diff(&args[2], &args[3]),
+        Some("watch") => return watch(registry()?, &args[2..]),
+        Some("download") => return download(registry()?, &args[2..]),
+        Some("verify") if args.len() > 2 => return verify(registry()?, &args[2], &args[3..]),
+        Some("identify") if args.len() == 3 => return identify(&args[2]),
+        #[cfg(feature = "consumer")]
+        Some("product") if args.len() == 3 => return product(&args[2]),
+        Some("unattend") => return unattend(&args[2..]),
+        Some(format @ ("quickemu" | "qemu" | "libvirt" | "packer")) => return vm(registry()?, format, &args[2..]),
+        Some(format @ ("metalink" | "aria2")) if args.len() > 2 => return export(registry()?, format, &args[2..]),
+        #[cfg(feature = "server")]
+        Some("serve") => return serve(registry()?, args.get(2).map_or("127.0.0.1:8080", String::as_str)),
+        _ => {}
+    }
+    let positional = match args.len() {
+        2..=4 => args[1..].iter().map(String::as_str).collect::<Vec<_>>(),
         _ => {
             println!(
                 "This program is free software: you can redistribute it and/or modify it under the 
@@ -24,16 +46,47 @@ For more information, run {} --license\n",
                 args[0]
             );
             eprintln!("Usage: {} [release] [language] [arch]", args[0]);
+            eprintln!("       {} [spec, e.g. enterprise/server-2022@de-de/x86_64]", args[0]);
+            eprintln!(
+                "       {} list [--family consumer|enterprise|server] [--lang language] [--arch arch] [--min release]",
+                args[0]
+            );
+            #[cfg(feature = "consumer")]
+            eprintln!("       {} product [product id]", args[0]);
+            eprintln!("       {} catalog [--remote]", args[0]);
+            eprintln!("       {} diff [old catalog] [new catalog]", args[0]);
+            eprintln!("       {} verify [file] [catalog...]", args[0]);
+            eprintln!("       {} identify [file]", args[0]);
+            eprintln!(
+                "       {} watch [--interval seconds] [--remote] [--webhook url] [--exec command] [release] [language] [arch]",
+                args[0]
+            );
+            eprintln!("       {} [metalink|aria2] [release] [language] [arch]", args[0]);
+            eprintln!(
+                "       {} unattend [--edition name] [--username name] [--password password] [--product-key key] [--layout uefi|bios|manual] [--winrm] [release] [language] [arch]",
+                args[0]
+            );
+            eprintln!(
+                "       {} download [--connections n] [--output path] [release] [language] [arch]",
+                args[0]
+            );
+            eprintln!(
+                "       {} [quickemu|qemu|libvirt|packer] [--dir path] [--disk-size gb] [--drivers] [--builder qemu|virtualbox|hyperv] [release] [language] [arch]",
+                args[0]
+            );
+            #[cfg(feature = "server")]
+            eprintln!("       {} serve [address]", args[0]);
+            eprintln!("Any command accepts --mirror [directory|url] to resolve images from a mirror first");
             std::process::exit(1);
         }
     };
 
-    if release == "--license" {
+    if positional[0] == "--license" {
         println!("{}", LICENSE);
         std::process::exit(0);
     }
 
-    match rido::WindowsData::new(release, language, arch) {
+    match parse_entry(&positional).and_then(|entry| registry()?.resolve(entry)) {
         Ok(release) => {
             if let Some(hash) = &release.hash {
                 println!("{} {}", release.url, hash);
@@ -48,3 +101,291 @@ For more information, run {} --license\n",
     };
     Ok(())
 }
+
+fn list(registry: rido::ProviderRegistry, args: &[String]) -> Result<(), rido::RidoError> {
+    let mut query = rido::EntryQuery::with_registry(registry);
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| invalid_argument(arg));
+        query = match arg {
+            "--family" => query.family(value.try_into()?),
+            "--lang" => query.lang(value),
+            "--arch" => query.arch(value.try_into()?),
+            "--min" => query.min_release(rido::WindowsRelease::try_from(value)?)?,
+            _ => invalid_argument(arg),
+        };
+    }
+    // Each line is a spec which can be passed back in as a release
+    for entry in query.iter() {
+        println!("{entry}");
+    }
+    Ok(())
+}
+
+fn catalog(registry: rido::ProviderRegistry, resolve_remote: bool) -> Result<(), rido::RidoError> {
+    let catalog = rido::Catalog::snapshot(&registry, resolve_remote);
+    for failure in &catalog.failures {
+        eprintln!("{}: {}", failure.key, failure.error);
+    }
+    println!("{}", catalog.to_json()?);
+    Ok(())
+}
+
+fn diff(old: &str, new: &str) -> Result<(), rido::RidoError> {
+    let old = rido::Catalog::from_json(&std::fs::read_to_string(old)?)?;
+    let new = rido::Catalog::from_json(&std::fs::read_to_string(new)?)?;
+    print!("{}", old.diff(&new));
+    Ok(())
+}
+
+#[cfg(feature = "server")]
+fn serve(registry: rido::ProviderRegistry, addr: &str) -> Result<(), rido::RidoError> {
+    eprintln!("Listening on {addr}");
+    rido::Server::new(registry, rido::ServerOptions::default()).serve(addr)
+}
+
+fn watch(registry: rido::ProviderRegistry, args: &[String]) -> Result<(), rido::RidoError> {
+    let mut watcher = rido::Watcher::new(registry, Vec::new()).on_change(|changes| print!("{changes}"));
+    let mut positional = Vec::new();
+
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next().unwrap_or_else(|| {
+                eprintln!("{arg} requires a value");
+                std::process::exit(1);
+            })
+        };
+        watcher = match arg {
+            "--remote" => watcher.resolve_remote(true),
+            "--interval" => match value().parse() {
+                Ok(seconds) => watcher.interval(std::time::Duration::from_secs(seconds)),
+                Err(_) => invalid_argument(arg),
+            },
+            "--webhook" => match value().parse() {
+                Ok(url) => watcher.webhook(url),
+                Err(_) => invalid_argument(arg),
+            },
+            "--exec" => {
+                let mut command = value().split_whitespace();
+                watcher.command(command.next().unwrap_or_default(), command)
+            }
+            _ => {
+                positional.push(arg);
+                watcher
+            }
+        };
+    }
+
+    // Watch a single entry if one is specified, otherwise everything
+    let entries = if positional.is_empty() { rido::WindowsEntry::list_all() } else { vec![parse_entry(&positional)?] };
+    watcher.entries(entries).run(|e| eprintln!("{e}"))
+}
+
+fn export(registry: rido::ProviderRegistry, format: &str, args: &[String]) -> Result<(), rido::RidoError> {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let mut data = registry.resolve(parse_entry(&args)?)?;
+    // Follow redirects so that the real filename and size are known
+    data.resolve_remote()?;
+    match format {
+        "metalink" => print!("{}", rido::export::metalink(&[data])),
+        _ => print!("{}", rido::export::aria2_input(&[data])),
+    }
+    Ok(())
+}
+
+fn download(registry: rido::ProviderRegistry, args: &[String]) -> Result<(), rido::RidoError> {
+    let mut downloader = rido::Downloader::new();
+    let mut output = None;
+    let mut positional = Vec::new();
+
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        match arg {
+            "--connections" => match args.next().and_then(|value| value.parse().ok()) {
+                Some(connections) => downloader = downloader.connections(connections),
+                None => invalid_argument(arg),
+            },
+            "--output" => output = Some(args.next().unwrap_or_else(|| invalid_argument(arg))),
+            _ => positional.push(arg),
+        }
+    }
+    if positional.is_empty() {
+        invalid_argument("release");
+    }
+
+    let mut data = registry.resolve(parse_entry(&positional)?)?;
+    data.resolve_remote()?;
+    let output = output.map_or_else(|| data.filename(), str::to_string);
+    eprintln!("Downloading {} to {output}", data.download_url());
+    downloader.download(&data, std::path::Path::new(&output))
+}
+
+fn verify(registry: rido::ProviderRegistry, file: &str, catalogs: &[String]) -> Result<(), rido::RidoError> {
+    let hash = rido::sha256_file(std::path::Path::new(file))?;
+    println!("{hash}  {file}");
+
+    let mut catalogs = catalogs
+        .iter()
+        .map(|catalog| rido::Catalog::from_json(&std::fs::read_to_string(catalog)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    if catalogs.is_empty() {
+        // Microsoft doesn't publish hashes, so without a catalog only a hashed mirror can identify the file
+        let entries = registry
+            .providers()
+            .filter(|provider| provider.capabilities().hashes)
+            .flat_map(|provider| provider.entries())
+            .collect::<Vec<_>>();
+        if entries.is_empty() {
+            eprintln!("No hashes are known; pass a catalog, or a mirror whose manifest includes hashes with --mirror");
+            std::process::exit(1);
+        }
+        catalogs.push(rido::Catalog::snapshot_entries(&registry, entries, false));
+    }
+
+    let mut found = false;
+    for catalog in &catalogs {
+        for image in catalog.find_hash(&hash) {
+            println!("Matches {}", image.key);
+            found = true;
+        }
+    }
+    if !found {
+        eprintln!("No known image matches this file");
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn identify(file: &str) -> Result<(), rido::RidoError> {
+    let identity = rido::IsoIdentity::from_path(file)?;
+    match &identity.entry {
+        Some(entry) => println!("{} ({}, {})", entry.release, entry.lang, entry.arch),
+        None => println!("Unrecognised release"),
+    }
+    println!("Version: {}", identity.version);
+    for edition in &identity.editions {
+        println!("{}: {} [{}]", edition.index, edition.name, edition.languages.join(", "));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "consumer")]
+fn product(id: &str) -> Result<(), rido::RidoError> {
+    let id = id.parse().unwrap_or_else(|_| invalid_argument("product id"));
+    let info = rido::ProductInfo::lookup(id)?;
+    println!("{} ({})", info.display_name, info.family);
+    if let Some(feature_update) = &info.version.feature_update {
+        println!("Feature update: {feature_update}");
+    }
+    let languages = info.languages.iter().map(ToString::to_string).collect::<Vec<_>>();
+    println!("Languages: {}", languages.join(", "));
+    Ok(())
+}
+
+fn unattend(args: &[String]) -> Result<(), rido::RidoError> {
+    use rido::unattend::{DiskLayout, UnattendOptions};
+    let mut options = UnattendOptions::default();
+    let mut positional = Vec::new();
+
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| invalid_argument(arg)).to_string();
+        match arg {
+            "--edition" => options.edition = Some(value()),
+            "--username" => options.username = value(),
+            "--password" => options.password = Some(value()),
+            "--product-key" => options.product_key = Some(value()),
+            "--winrm" => options.winrm = true,
+            "--layout" => {
+                options.disk_layout = Some(match value().as_str() {
+                    "uefi" => DiskLayout::Uefi,
+                    "bios" => DiskLayout::Bios,
+                    "manual" => DiskLayout::Manual,
+                    _ => invalid_argument(arg),
+                })
+            }
+            _ => positional.push(arg),
+        }
+    }
+    if positional.is_empty() {
+        invalid_argument("release");
+    }
+    let entry = parse_entry(&positional)?;
+    // Server setup stops to ask for an Administrator password if the answer file doesn't set one
+    if entry.release.family() == rido::ReleaseFamily::Server && options.password.is_none() {
+        eprintln!("Server releases require --password");
+        std::process::exit(1);
+    }
+    print!("{}", rido::unattend::autounattend(&entry, &options));
+    Ok(())
+}
+
+fn vm(registry: rido::ProviderRegistry, format: &str, args: &[String]) -> Result<(), rido::RidoError> {
+    let mut directory = ".";
+    let mut disk_size = None;
+    let mut builder = rido::vm::PackerBuilder::Qemu;
+    let mut drivers = false;
+    let mut positional = Vec::new();
+
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        match arg {
+            "--dir" => directory = args.next().unwrap_or_else(|| invalid_argument(arg)),
+            "--drivers" => drivers = true,
+            "--disk-size" => match args.next().and_then(|value| value.parse().ok()) {
+                Some(size) => disk_size = Some(size),
+                None => invalid_argument(arg),
+            },
+            "--builder" => {
+                builder = match args.next() {
+                    Some("qemu") => rido::vm::PackerBuilder::Qemu,
+                    Some("virtualbox") => rido::vm::PackerBuilder::VirtualBox,
+                    Some("hyperv") => rido::vm::PackerBuilder::HyperV,
+                    _ => invalid_argument(arg),
+                }
+            }
+            _ => positional.push(arg),
+        }
+    }
+    if positional.is_empty() {
+        invalid_argument("release");
+    }
+
+    let mut data = registry.resolve(parse_entry(&positional)?)?;
+    data.resolve_remote()?;
+    let mut config = rido::vm::VmConfig::from_data(&data, directory);
+    if let Some(size) = disk_size {
+        config.disk_size_gb = size;
+    }
+    if drivers {
+        config.driver_iso = Some(std::path::Path::new(directory).join("virtio-win.iso"));
+    }
+    match format {
+        "quickemu" => print!("{}", config.to_quickemu()),
+        "libvirt" => print!("{}", config.to_libvirt()),
+        "packer" => print!("{}", config.to_packer(builder, data.download_url(), data.hash.as_deref())),
+        _ => print!("{}", config.qemu_command()),
+    }
+    Ok(())
+}
+
+fn registry(mirror: Option<&str>) -> Result<rido::ProviderRegistry, rido::RidoError> {
+    let mut registry = rido::ProviderRegistry::default();
+    if let Some(location) = mirror {
+        registry.register_first(rido::MirrorProvider::load(rido::MirrorLocation::parse(location)?)?);
+    }
+    Ok(registry)
+}
+
+fn parse_entry(args: &[&str]) -> Result<rido::WindowsEntry, rido::RidoError> {
+    match args {
+        [spec] => spec.parse(),
+        _ => rido::WindowsEntry::new(args[0], args[1], args.get(2).copied().unwrap_or("x86_64")),
+    }
+}
+
+fn invalid_argument(arg: &str) -> ! {
+    eprintln!("Invalid value for {arg}");
+    std::process::exit(1);
+}
@@ -0,0 +1,202 @@
+use crate::{Provider, ProviderCapabilities, ResolvedImage, RidoError, WindowsArchitecture, WindowsEntry, WindowsLanguage, WindowsRelease, WindowsVersion};
+use reqwest::Url;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// Where a mirror's images are stored. Paths in the manifest are relative to this location, so HTTP URLs
+/// should end with a trailing slash.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MirrorLocation {
+    Directory(PathBuf),
+    Http(Url),
+}
+
+impl MirrorLocation {
+    /// An HTTP(S) URL, or otherwise a directory path
+    pub fn parse(location: &str) -> Result<Self, RidoError> {
+        if location.starts_with("http://") || location.starts_with("https://") {
+            // Manifest paths are joined onto the URL, which only keeps its last segment if it ends in a slash
+            let location = if location.ends_with('/') { location.to_string() } else { format!("{location}/") };
+            Url::parse(&location)
+                .map(Self::Http)
+                .map_err(|e| RidoError::MirrorManifest(e.to_string()))
+        } else {
+            Ok(Self::Directory(PathBuf::from(location)))
+        }
+    }
+}
+
+/// Resolves entries against a local directory or HTTP file server, using a JSON manifest such as:
+/// ```json
+/// [
+///     {
+///         "release": "11",
+///         "language": "English (United States)",
+///         "arch": "x86_64",
+///         "path": "windows-11/Win11_24H2_English_x64.iso",
+///         "hash": "b56b911bf18a2ceaeb3904d87e7c770bdf92d3099599d61ac2497b91bf190b11",
+///         "size": 5819484160
+///     }
+/// ]
+/// ```
+#[derive(Debug, Clone)]
+pub struct MirrorProvider {
+    location: MirrorLocation,
+    images: Vec<(WindowsEntry, MirrorImage)>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ManifestEntry {
+    release: String,
+    language: String,
+    arch: String,
+    #[serde(flatten)]
+    image: MirrorImage,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MirrorImage {
+    path: String,
+    hash: Option<String>,
+    size: Option<u64>,
+    #[serde(default)]
+    version: WindowsVersion,
+}
+
+impl MirrorProvider {
+    pub const MANIFEST_NAME: &'static str = "manifest.json";
+
+    pub fn from_manifest(location: MirrorLocation, manifest: &str) -> Result<Self, RidoError> {
+        let manifest: Vec<ManifestEntry> = serde_json::from_str(manifest).map_err(RidoError::JSONParsing)?;
+        let images = manifest
+            .into_iter()
+            .map(|entry| {
+                let invalid = |field: &str, value: &str| RidoError::MirrorManifest(format!("invalid {field} '{value}' for image {}", entry.image.path));
+                let release = WindowsRelease::try_from(entry.release.as_str()).map_err(|_| invalid("release", &entry.release))?;
+                let lang = WindowsLanguage::try_from((release, entry.language.as_str())).map_err(|_| invalid("language", &entry.language))?;
+                let arch = WindowsArchitecture::try_from(entry.arch.as_str()).map_err(|_| invalid("architecture", &entry.arch))?;
+                let windows_entry = WindowsEntry { release, lang, arch };
+                windows_entry
+                    .validate()
+                    .map_err(|e| RidoError::MirrorManifest(format!("{e} (image {})", entry.image.path)))?;
+                Ok((windows_entry, entry.image))
+            })
+            .collect::<Result<_, RidoError>>()?;
+        Ok(Self { location, images })
+    }
+
+    /// Load the manifest stored at the root of the mirror
+    pub fn load(location: MirrorLocation) -> Result<Self, RidoError> {
+        let manifest = match &location {
+            MirrorLocation::Directory(dir) => std::fs::read_to_string(dir.join(Self::MANIFEST_NAME))?,
+            MirrorLocation::Http(base) => {
+                let url = base
+                    .join(Self::MANIFEST_NAME)
+                    .map_err(|e| RidoError::MirrorManifest(e.to_string()))?;
+                reqwest::blocking::get(url)?.error_for_status()?.text()?
+            }
+        };
+        Self::from_manifest(location, &manifest)
+    }
+
+    fn image(&self, entry: &WindowsEntry) -> Option<&MirrorImage> {
+        self.images.iter().find(|(e, _)| e == entry).map(|(_, image)| image)
+    }
+}
+
+impl Provider for MirrorProvider {
+    fn name(&self) -> &str {
+        "mirror"
+    }
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            hashes: self.images.iter().any(|(_, image)| image.hash.is_some()),
+            versions: self.images.iter().any(|(_, image)| image.version != WindowsVersion::default()),
+            redirects: false,
+            network: matches!(self.location, MirrorLocation::Http(_)),
+        }
+    }
+    fn entries(&self) -> Vec<WindowsEntry> {
+        self.images.iter().map(|(entry, _)| entry.clone()).collect()
+    }
+    fn supports(&self, entry: &WindowsEntry) -> bool {
+        self.image(entry).is_some()
+    }
+    fn resolve(&self, entry: &WindowsEntry) -> Result<ResolvedImage, RidoError> {
+        let image = self
+            .image(entry)
+            .ok_or(RidoError::NoProvider(entry.release, entry.lang, entry.arch))?;
+        let url = match &self.location {
+            MirrorLocation::Directory(dir) => {
+                let path = dir.join(&image.path);
+                if !path.is_file() {
+                    return Err(RidoError::MirrorMissing(path));
+                }
+                let path = path.canonicalize()?;
+                Url::from_file_path(&path)
+                    .map_err(|_| RidoError::MirrorMissing(path))?
+                    .to_string()
+            }
+            MirrorLocation::Http(base) => base
+                .join(&image.path)
+                .map_err(|e| RidoError::MirrorManifest(e.to_string()))?
+                .to_string(),
+        };
+        Ok(ResolvedImage {
+            url,
+            hash: image.hash.clone(),
+            size: image.size,
+            version: image.version.clone(),
+        })
+    }
+}
+
+// The test mirror serves a consumer release
+#[cfg(all(test, feature = "consumer"))]
+mod tests {
+    use super::*;
+    use crate::{Downloader, ProviderRegistry};
+
+    fn directory_mirror(name: &str, contents: &[u8]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rido-mirror-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("windows-11")).unwrap();
+        std::fs::write(dir.join("windows-11/image.iso"), contents).unwrap();
+        let manifest = format!(
+            r#"[{{"release": "11", "language": "English (United States)", "arch": "x86_64", "path": "windows-11/image.iso", "hash": "{}"}}]"#,
+            crate::checksum::sha256_file(&dir.join("windows-11/image.iso")).unwrap()
+        );
+        std::fs::write(dir.join(MirrorProvider::MANIFEST_NAME), manifest).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parse_location() {
+        assert_eq!(
+            MirrorLocation::parse("https://mirror.example/windows").unwrap(),
+            MirrorLocation::Http(Url::parse("https://mirror.example/windows/").unwrap())
+        );
+        assert_eq!(
+            MirrorLocation::parse("/srv/windows").unwrap(),
+            MirrorLocation::Directory(PathBuf::from("/srv/windows"))
+        );
+    }
+
+    #[test]
+    fn directory_images_resolve_and_download() {
+        let dir = directory_mirror("download", b"not really an iso");
+        let mut registry = ProviderRegistry::default();
+        registry.register_first(MirrorProvider::load(MirrorLocation::Directory(dir.clone())).unwrap());
+
+        let entry = WindowsEntry::new("11", "English (United States)", "x86_64").unwrap();
+        let mut data = registry.resolve(entry).unwrap();
+        assert!(data.url.starts_with("file://"));
+        data.resolve_remote().unwrap();
+        assert_eq!(data.filename(), "image.iso");
+        assert_eq!(data.remote.as_ref().and_then(|remote| remote.content_length), Some(17));
+
+        let output = dir.join("copy.iso");
+        Downloader::new().download(&data, &output).unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), b"not really an iso");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{RidoError, WindowsData, WindowsEntry, WindowsVersion};
+
+/// A source of Windows images. Consumer and enterprise releases are provided by [`ConsumerProvider`](crate::ConsumerProvider)
+/// and [`EnterpriseProvider`](crate::EnterpriseProvider); other sources, such as an internal mirror, can implement this trait
+/// and be added to a [`ProviderRegistry`].
+pub trait Provider: Send + Sync {
+    fn name(&self) -> &str;
+    fn capabilities(&self) -> ProviderCapabilities;
+    /// Every entry this provider is able to resolve
+    fn entries(&self) -> Vec<WindowsEntry>;
+    fn supports(&self, entry: &WindowsEntry) -> bool {
+        self.entries().contains(entry)
+    }
+    fn resolve(&self, entry: &WindowsEntry) -> Result<ResolvedImage, RidoError>;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProviderCapabilities {
+    /// Resolved images include a checksum
+    pub hashes: bool,
+    /// Resolved images include version information
+    pub versions: bool,
+    /// Resolved URLs are redirects, which must be followed to find the final download
+    pub redirects: bool,
+    /// Resolving an entry requires network access
+    pub network: bool,
+}
+
+/// The result of resolving a single entry
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResolvedImage {
+    pub url: String,
+    pub hash: Option<String>,
+    /// Size of the image in bytes, if known
+    pub size: Option<u64>,
+    pub version: WindowsVersion,
+}
+
+/// An ordered list of providers. When resolving an entry, each provider which supports it is tried in order,
+/// so that e.g. a mirror can fall back to Microsoft's servers.
+pub struct ProviderRegistry {
+    providers: Vec<Box<dyn Provider>>,
+}
+
+impl Default for ProviderRegistry {
+    /// A registry containing the built-in providers enabled by crate features
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        #[cfg(feature = "consumer")]
+        registry.register(crate::ConsumerProvider);
+        #[cfg(feature = "enterprise")]
+        registry.register(crate::EnterpriseProvider);
+        registry
+    }
+}
+
+impl ProviderRegistry {
+    pub fn empty() -> Self {
+        Self { providers: Vec::new() }
+    }
+    /// Add a provider to the end of the registry
+    pub fn register(&mut self, provider: impl Provider + 'static) -> &mut Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+    /// Add a provider which takes precedence over those already registered
+    pub fn register_first(&mut self, provider: impl Provider + 'static) -> &mut Self {
+        self.providers.insert(0, Box::new(provider));
+        self
+    }
+    pub fn providers(&self) -> impl Iterator<Item = &dyn Provider> {
+        self.providers.iter().map(AsRef::as_ref)
+    }
+    pub fn provider_for(&self, entry: &WindowsEntry) -> Option<&dyn Provider> {
+        self.providers().find(|provider| provider.supports(entry))
+    }
+    /// All entries offered by registered providers, without duplicates
+    pub fn list_all(&self) -> Vec<WindowsEntry> {
+        let mut entries = Vec::new();
+        for entry in self.providers().flat_map(|provider| provider.entries()) {
+            if !entries.contains(&entry) {
+                entries.push(entry);
+            }
+        }
+        entries
+    }
+    pub fn resolve(&self, entry: WindowsEntry) -> Result<WindowsData, RidoError> {
+        entry.validate()?;
+        let mut error = None;
+        for provider in self.providers().filter(|provider| provider.supports(&entry)) {
+            match provider.resolve(&entry) {
+                Ok(ResolvedImage { url, hash, size, version }) => {
+                    return Ok(WindowsData {
+                        info: entry,
+                        url,
+                        hash,
+                        size,
+                        version,
+                        remote: None,
+                    })
+                }
+                Err(e) => error = Some(e),
+            }
+        }
+        Err(error.unwrap_or(RidoError::NoProvider(entry.release, entry.lang, entry.arch)))
+    }
+}
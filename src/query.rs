@@ -0,0 +1,175 @@
+use crate::{ProviderRegistry, RidoError, WindowsArchitecture, WindowsEntry, WindowsRelease};
+use std::collections::HashSet;
+use strum_macros::{Display, EnumIter};
+
+#[derive(EnumIter, Display, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[strum(serialize_all = "lowercase")]
+pub enum ReleaseFamily {
+    Consumer,
+    Enterprise,
+    Server,
+}
+
+impl TryFrom<&str> for ReleaseFamily {
+    type Error = RidoError;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "consumer" => Self::Consumer,
+            "enterprise" => Self::Enterprise,
+            "server" => Self::Server,
+            _ => return Err(RidoError::InvalidReleaseStr),
+        })
+    }
+}
+
+/// Filters the entries offered by a registry, e.g. every x86_64 server release in German:
+///
+/// ```ignore
+/// let entries = EntryQuery::new().family(ReleaseFamily::Server).lang("de-DE").arch(WindowsArchitecture::x86_64).iter();
+/// ```
+pub struct EntryQuery {
+    registry: ProviderRegistry,
+    family: Option<ReleaseFamily>,
+    lang: Option<String>,
+    arch: Option<WindowsArchitecture>,
+    min_release: Option<WindowsRelease>,
+}
+
+impl Default for EntryQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EntryQuery {
+    /// A query over the built-in providers, which matches every entry
+    pub fn new() -> Self {
+        Self::with_registry(ProviderRegistry::default())
+    }
+    pub fn with_registry(registry: ProviderRegistry) -> Self {
+        Self {
+            registry,
+            family: None,
+            lang: None,
+            arch: None,
+            min_release: None,
+        }
+    }
+    pub fn family(mut self, family: ReleaseFamily) -> Self {
+        self.family = Some(family);
+        self
+    }
+    /// Match a language by name or locale tag, e.g. 'German' or 'de-DE', across consumer and enterprise releases
+    pub fn lang(mut self, lang: impl Into<String>) -> Self {
+        self.lang = Some(lang.into());
+        self
+    }
+    pub fn arch(mut self, arch: WindowsArchitecture) -> Self {
+        self.arch = Some(arch);
+        self
+    }
+    /// Only match releases at least as new as the given one, compared by their base build. Fails for releases whose
+    /// base build isn't known, such as custom product IDs.
+    pub fn min_release(mut self, release: impl Into<WindowsRelease>) -> Result<Self, RidoError> {
+        let release = release.into();
+        if release.base_build().is_none() {
+            return Err(RidoError::UnknownBuild(release));
+        }
+        self.min_release = Some(release);
+        Ok(self)
+    }
+
+    pub fn matches(&self, entry: &WindowsEntry) -> bool {
+        self.family.is_none_or(|family| entry.release.family() == family)
+            && self.arch.is_none_or(|arch| entry.arch == arch)
+            && self
+                .lang
+                .as_ref()
+                .is_none_or(|lang| entry.lang.to_string().eq_ignore_ascii_case(lang) || entry.lang.locale().eq_ignore_ascii_case(lang))
+            && self
+                .min_release
+                .and_then(|release| release.base_build())
+                .is_none_or(|min| entry.release.base_build().is_some_and(|build| build >= min))
+    }
+
+    /// Every matching entry, in the order the registry lists them. Each provider is only asked for its entries once
+    /// the iterator reaches it.
+    pub fn iter(self) -> impl Iterator<Item = WindowsEntry> {
+        let mut provider = 0;
+        let mut entries = Vec::<WindowsEntry>::new().into_iter();
+        // Providers may offer the same entry, e.g. a mirror and Microsoft
+        let mut seen = HashSet::new();
+        std::iter::from_fn(move || loop {
+            match entries.next() {
+                Some(entry) => {
+                    if seen.insert(entry.clone()) && self.matches(&entry) {
+                        return Some(entry);
+                    }
+                }
+                None => {
+                    entries = self.registry.providers().nth(provider)?.entries().into_iter();
+                    provider += 1;
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Provider, ProviderCapabilities, ResolvedImage};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    /// Offers a fixed list of entries, counting how many times it's asked for them
+    struct ListProvider(Vec<WindowsEntry>, Arc<AtomicUsize>);
+
+    impl Provider for ListProvider {
+        fn name(&self) -> &str {
+            "list"
+        }
+        fn capabilities(&self) -> ProviderCapabilities {
+            ProviderCapabilities::default()
+        }
+        fn entries(&self) -> Vec<WindowsEntry> {
+            self.1.fetch_add(1, Ordering::SeqCst);
+            self.0.clone()
+        }
+        fn resolve(&self, _: &WindowsEntry) -> Result<ResolvedImage, RidoError> {
+            Ok(ResolvedImage::default())
+        }
+    }
+
+    #[test]
+    fn iter_is_lazy_and_deduplicated() {
+        let all = WindowsEntry::list_all();
+        let (first_calls, second_calls) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let mut registry = ProviderRegistry::empty();
+        registry.register(ListProvider(all[..3].to_vec(), first_calls.clone()));
+        registry.register(ListProvider(all[1..5].to_vec(), second_calls.clone()));
+
+        let mut entries = EntryQuery::with_registry(registry).iter();
+        assert_eq!(entries.next().as_ref(), Some(&all[0]));
+        assert_eq!(
+            (first_calls.load(Ordering::SeqCst), second_calls.load(Ordering::SeqCst)),
+            (1, 0)
+        );
+        assert_eq!(entries.collect::<Vec<_>>(), all[1..5]);
+        assert_eq!(second_calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "consumer")]
+    fn min_release() {
+        let eleven = WindowsRelease::try_from("11").unwrap();
+        let entries = EntryQuery::new().min_release(eleven).unwrap().iter().collect::<Vec<_>>();
+        assert!(!entries.is_empty());
+        assert!(entries.iter().all(|entry| entry.release.base_build() >= eleven.base_build()));
+
+        let custom = WindowsRelease::try_from("productid:3113").unwrap();
+        assert!(matches!(EntryQuery::new().min_release(custom), Err(RidoError::UnknownBuild(_))));
+    }
+}
@@ -0,0 +1,183 @@
+use crate::RidoError;
+use reqwest::{
+    blocking::{Client, Response},
+    header::{HeaderMap, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, ETAG, LAST_MODIFIED, RANGE},
+};
+
+/// Metadata about the file a download URL ultimately points to, gathered without downloading it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteMetadata {
+    /// The URL at the end of the redirect chain
+    pub final_url: String,
+    pub filename: Option<String>,
+    pub content_length: Option<u64>,
+    pub last_modified: Option<String>,
+    pub etag: Option<String>,
+}
+
+pub(crate) fn fetch_metadata(client: &Client, url: &str) -> Result<RemoteMetadata, RidoError> {
+    // Directory mirrors resolve to file URLs, which reqwest can't fetch
+    if let Some(path) = file_path(url) {
+        let metadata = std::fs::metadata(&path)?;
+        return Ok(RemoteMetadata {
+            final_url: url.to_string(),
+            filename: path.file_name().map(|name| name.to_string_lossy().into_owned()),
+            content_length: Some(metadata.len()),
+            last_modified: None,
+            etag: None,
+        });
+    }
+    // The blocking client follows redirects by default, so the response URL is the final one
+    let response = client.head(url).send()?.error_for_status()?;
+    Ok(RemoteMetadata::from_response(&response))
+}
+
+/// The result of probing a download URL: its size and filename, along with the build information
+/// embedded in the filename where available.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageProbe {
+    pub metadata: RemoteMetadata,
+    pub build: Option<ImageBuild>,
+}
+
+/// Build information embedded in Microsoft's ISO filenames, e.g.
+/// '26100.1742.240906-0331.ge_release_svc_refresh_CLIENT_CONSUMER_x64FRE_en-us.iso'
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageBuild {
+    pub build: u32,
+    pub revision: u32,
+    pub branch: String,
+    pub date: BuildDate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BuildDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+}
+
+/// The local path of a `file://` URL
+pub(crate) fn file_path(url: &str) -> Option<std::path::PathBuf> {
+    reqwest::Url::parse(url)
+        .ok()
+        .filter(|url| url.scheme() == "file")?
+        .to_file_path()
+        .ok()
+}
+
+pub(crate) fn probe(client: &Client, url: &str) -> Result<ImageProbe, RidoError> {
+    let metadata = match fetch_metadata(client, url) {
+        Ok(metadata) if metadata.content_length.is_some() => metadata,
+        // Some servers reject HEAD requests or omit the length; a single byte ranged GET reports the full size in Content-Range
+        _ => {
+            let response = client.get(url).header(RANGE, "bytes=0-0").send()?.error_for_status()?;
+            let mut metadata = RemoteMetadata::from_response(&response);
+            if let Some(total) = header_str(response.headers(), CONTENT_RANGE).and_then(|range| range.rsplit_once('/')?.1.parse().ok()) {
+                metadata.content_length = Some(total);
+            }
+            metadata
+        }
+    };
+    let build = metadata.filename.as_deref().and_then(ImageBuild::from_filename);
+    Ok(ImageProbe { metadata, build })
+}
+
+impl ImageBuild {
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        let mut parts = filename.splitn(4, '.');
+        let build = parts.next()?.parse().ok()?;
+        let revision = parts.next()?.parse().ok()?;
+
+        // Build date is formatted as 'yymmdd-hhmm'
+        let (date, time) = parts.next()?.split_once('-')?;
+        let field = |text: &str, range: std::ops::Range<usize>| text.get(range)?.parse::<u8>().ok();
+        if date.len() != 6 || time.len() != 4 {
+            return None;
+        }
+        let date = BuildDate {
+            year: 2000 + field(date, 0..2)? as u16,
+            month: field(date, 2..4)?,
+            day: field(date, 4..6)?,
+            hour: field(time, 0..2)?,
+            minute: field(time, 2..4)?,
+        };
+
+        // The branch is made up of the lowercase tokens preceding the edition, e.g. 'ge_release_svc_refresh'
+        let branch = parts
+            .next()?
+            .split('_')
+            .take_while(|token| !token.chars().any(|c| c.is_ascii_uppercase()))
+            .collect::<Vec<_>>()
+            .join("_");
+        if branch.is_empty() {
+            return None;
+        }
+
+        Some(Self { build, revision, branch, date })
+    }
+}
+
+impl RemoteMetadata {
+    pub(crate) fn from_response(response: &Response) -> Self {
+        let headers = response.headers();
+        let final_url = response.url().to_string();
+        let filename = content_disposition_filename(headers).or_else(|| {
+            response
+                .url()
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+        });
+        Self {
+            final_url,
+            filename,
+            content_length: header_str(headers, CONTENT_LENGTH).and_then(|len| len.parse().ok()),
+            last_modified: header_str(headers, LAST_MODIFIED).map(str::to_string),
+            etag: header_str(headers, ETAG).map(str::to_string),
+        }
+    }
+}
+
+fn header_str(headers: &HeaderMap, name: reqwest::header::HeaderName) -> Option<&str> {
+    headers.get(name)?.to_str().ok()
+}
+
+fn content_disposition_filename(headers: &HeaderMap) -> Option<String> {
+    // e.g. 'attachment; filename="Win11_24H2_English_x64.iso"'
+    header_str(headers, CONTENT_DISPOSITION)?
+        .split(';')
+        .filter_map(|param| param.trim().split_once('='))
+        .find(|(key, _)| key.eq_ignore_ascii_case("filename"))
+        .map(|(_, value)| value.trim_matches('"').to_string())
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_build_from_filename() {
+        let build = ImageBuild::from_filename("26100.1742.240906-0331.ge_release_svc_refresh_CLIENT_CONSUMER_x64FRE_en-us.iso").unwrap();
+        assert_eq!(
+            build,
+            ImageBuild {
+                build: 26100,
+                revision: 1742,
+                branch: "ge_release_svc_refresh".to_string(),
+                date: BuildDate {
+                    year: 2024,
+                    month: 9,
+                    day: 6,
+                    hour: 3,
+                    minute: 31,
+                },
+            }
+        );
+        assert_eq!(ImageBuild::from_filename("Win11_24H2_English_x64.iso"), None);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{CatalogImage, CatalogKey, ProviderRegistry, RidoError, WindowsEntry};
+use reqwest::Url;
+use serde_json::json;
+use std::{
+    collections::{HashMap, VecDeque},
+    net::ToSocketAddrs,
+    time::{Duration, Instant},
+};
+use tiny_http::{Header, Method, Response};
+
+/// A small HTTP service exposing entry listing and resolution as JSON:
+/// - `GET /entries` lists every available entry
+/// - `GET /resolve?release=&lang=&arch=` resolves an entry. `lang` and `arch` default to English (United States) and x86_64.
+///   With `redirect=true`, the response is a 302 redirect straight to the image.
+pub struct Server {
+    registry: ProviderRegistry,
+    options: ServerOptions,
+    cache: HashMap<WindowsEntry, (Instant, CatalogImage)>,
+    requests: VecDeque<Instant>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerOptions {
+    /// How long resolved URLs are reused before being resolved again
+    pub cache_ttl: Duration,
+    /// Maximum number of upstream resolutions within `rate_window`, so that Microsoft doesn't block the host
+    pub rate_limit: usize,
+    pub rate_window: Duration,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            cache_ttl: Duration::from_secs(60 * 60),
+            rate_limit: 10,
+            rate_window: Duration::from_secs(60),
+        }
+    }
+}
+
+enum Reply {
+    Json(u16, serde_json::Value),
+    Redirect(String),
+}
+
+impl Server {
+    pub fn new(registry: ProviderRegistry, options: ServerOptions) -> Self {
+        Self {
+            registry,
+            options,
+            cache: HashMap::new(),
+            requests: VecDeque::new(),
+        }
+    }
+
+    /// Listen on the given address and serve requests until the process exits
+    pub fn serve(mut self, addr: impl ToSocketAddrs) -> Result<(), RidoError> {
+        let server = tiny_http::Server::http(addr).map_err(RidoError::Server)?;
+        for request in server.incoming_requests() {
+            let reply = match request.method() {
+                Method::Get | Method::Head => self.handle(request.url()),
+                _ => Reply::Json(405, json!({ "error": "Method not allowed" })),
+            };
+            let response = match reply {
+                Reply::Json(status, body) => Response::from_string(body.to_string())
+                    .with_status_code(status)
+                    .with_header(Header::from_bytes("Content-Type", "application/json").unwrap())
+                    .boxed(),
+                Reply::Redirect(url) => match Header::from_bytes("Location", url) {
+                    Ok(location) => Response::empty(302).with_header(location).boxed(),
+                    Err(_) => Response::empty(500).boxed(),
+                },
+            };
+            // A client disconnecting early isn't a reason to stop serving
+            let _ = request.respond(response);
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, path: &str) -> Reply {
+        let Ok(url) = Url::parse("http://localhost").and_then(|base| base.join(path)) else {
+            return Reply::Json(400, json!({ "error": "Invalid request URL" }));
+        };
+        let query = |key: &str| url.query_pairs().find(|(k, _)| k == key).map(|(_, v)| v.into_owned());
+        match url.path() {
+            "/entries" => {
+                let entries = self.registry.list_all().iter().map(CatalogKey::from).collect::<Vec<_>>();
+                Reply::Json(200, json!(entries))
+            }
+            "/resolve" => {
+                // Either a single spec, or separate release, language and architecture parameters
+                let entry = match (query("entry"), query("release")) {
+                    (Some(spec), _) => spec.parse::<WindowsEntry>(),
+                    (None, Some(release)) => {
+                        let lang = query("lang").unwrap_or_else(|| "English (United States)".to_string());
+                        let arch = query("arch").unwrap_or_else(|| "x86_64".to_string());
+                        WindowsEntry::new(release.as_str(), lang.as_str(), arch.as_str())
+                    }
+                    (None, None) => return Reply::Json(400, json!({ "error": "Missing release parameter" })),
+                };
+                let redirect = matches!(query("redirect").as_deref(), Some("true" | "1"));
+
+                let image = entry.and_then(|entry| self.resolve(entry));
+                match image {
+                    Ok(image) if redirect => Reply::Redirect(image.url),
+                    Ok(image) => Reply::Json(200, json!(image)),
+                    Err(e) => Reply::Json(error_status(&e), json!({ "error": e.to_string() })),
+                }
+            }
+            _ => Reply::Json(404, json!({ "error": "Not found" })),
+        }
+    }
+
+    fn resolve(&mut self, entry: WindowsEntry) -> Result<CatalogImage, RidoError> {
+        let now = Instant::now();
+        if let Some((resolved, image)) = self.cache.get(&entry) {
+            if now.duration_since(*resolved) < self.options.cache_ttl {
+                return Ok(image.clone());
+            }
+        }
+
+        while self
+            .requests
+            .front()
+            .is_some_and(|time| now.duration_since(*time) >= self.options.rate_window)
+        {
+            self.requests.pop_front();
+        }
+        if self.requests.len() >= self.options.rate_limit {
+            return Err(RidoError::RateLimited);
+        }
+        self.requests.push_back(now);
+
+        let image = CatalogImage::from(self.registry.resolve(entry.clone())?);
+        self.cache.insert(entry, (now, image.clone()));
+        Ok(image)
+    }
+}
+
+fn error_status(error: &RidoError) -> u16 {
+    match error {
+        RidoError::InvalidReleaseStr
+        | RidoError::InvalidLanguageStr
+        | RidoError::InvalidArchitectureStr
+        | RidoError::InvalidSelection
+        | RidoError::InvalidLanguage { .. }
+        | RidoError::InvalidArchitecture { .. }
+        | RidoError::UnknownProduct(..)
+        | RidoError::NoProvider(..) => 400,
+        RidoError::ProductIdUnavailable(_) => 404,
+        RidoError::RateLimited => 429,
+        _ => 502,
+    }
+}
@@ -0,0 +1,316 @@
+//! Generation of Autounattend.xml answer files whose locale and architecture settings match a [`WindowsEntry`],
+//! so that the answer file can never drift apart from the ISO it's used with.
+
+use crate::{export::escape_xml, ReleaseFamily, WindowsArchitecture, WindowsEntry};
+use std::fmt::Write;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnattendOptions {
+    /// The edition to install, as named in the install image, e.g. 'Windows 11 Pro'. Setup prompts if unset.
+    pub edition: Option<String>,
+    pub username: String,
+    /// Also used for the built-in Administrator account on server releases, which setup won't create without one
+    pub password: Option<String>,
+    pub product_key: Option<String>,
+    /// How the first disk is partitioned. If unset, it's chosen based on the release's firmware requirements.
+    pub disk_layout: Option<DiskLayout>,
+    /// Log in once after setup and enable WinRM over HTTP, so that tools such as Packer can connect as the account
+    pub winrm: bool,
+}
+
+impl Default for UnattendOptions {
+    fn default() -> Self {
+        Self {
+            edition: None,
+            username: "User".to_string(),
+            password: None,
+            product_key: None,
+            disk_layout: None,
+            winrm: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiskLayout {
+    /// Wipe the first disk and create a GPT layout with EFI, MSR and Windows partitions
+    Uefi,
+    /// Wipe the first disk and create an MBR layout with system reserved and Windows partitions
+    Bios,
+    /// Leave partitioning to the user
+    Manual,
+}
+
+impl DiskLayout {
+    pub fn for_entry(entry: &WindowsEntry) -> Self {
+        // 32-bit images are most likely to be installed on legacy BIOS systems
+        match entry.arch {
+            WindowsArchitecture::i686 => Self::Bios,
+            WindowsArchitecture::x86_64 => Self::Uefi,
+        }
+    }
+}
+
+/// Generate an Autounattend.xml answer file for the given entry
+pub fn autounattend(entry: &WindowsEntry, options: &UnattendOptions) -> String {
+    let locale = entry.lang.locale();
+    let input_locale = input_locale(locale);
+    let arch = match entry.arch {
+        WindowsArchitecture::x86_64 => "amd64",
+        WindowsArchitecture::i686 => "x86",
+    };
+    let component = |name: &str| {
+        format!(
+            r#"<component name="{name}" processorArchitecture="{arch}" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS" xmlns:wcm="http://schemas.microsoft.com/WMIConfig/2002/State" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">"#
+        )
+    };
+    let layout = options.disk_layout.unwrap_or_else(|| DiskLayout::for_entry(entry));
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<unattend xmlns=\"urn:schemas-microsoft-com:unattend\">\n");
+
+    xml.push_str("  <settings pass=\"windowsPE\">\n");
+    let _ = writeln!(xml, "    {}", component("Microsoft-Windows-International-Core-WinPE"));
+    let _ = writeln!(
+        xml,
+        "      <SetupUILanguage>\n        <UILanguage>{locale}</UILanguage>\n      </SetupUILanguage>"
+    );
+    push_locales(&mut xml, locale, input_locale);
+    xml.push_str("    </component>\n");
+
+    let _ = writeln!(xml, "    {}", component("Microsoft-Windows-Setup"));
+    push_disk_configuration(&mut xml, layout);
+    xml.push_str("      <ImageInstall>\n        <OSImage>\n");
+    if let Some(edition) = &options.edition {
+        let _ = writeln!(
+            xml,
+            "          <InstallFrom>\n            <MetaData wcm:action=\"add\">\n              <Key>/IMAGE/NAME</Key>\n              <Value>{}</Value>\n            </MetaData>\n          </InstallFrom>",
+            escape_xml(edition)
+        );
+    }
+    match layout {
+        DiskLayout::Uefi => xml.push_str("          <InstallTo>\n            <DiskID>0</DiskID>\n            <PartitionID>3</PartitionID>\n          </InstallTo>\n"),
+        DiskLayout::Bios => xml.push_str("          <InstallTo>\n            <DiskID>0</DiskID>\n            <PartitionID>2</PartitionID>\n          </InstallTo>\n"),
+        DiskLayout::Manual => {}
+    }
+    xml.push_str("        </OSImage>\n      </ImageInstall>\n");
+    xml.push_str("      <UserData>\n        <AcceptEula>true</AcceptEula>\n");
+    if let Some(key) = &options.product_key {
+        let _ = writeln!(
+            xml,
+            "        <ProductKey>\n          <Key>{}</Key>\n          <WillShowUI>OnError</WillShowUI>\n        </ProductKey>",
+            escape_xml(key)
+        );
+    }
+    xml.push_str("      </UserData>\n    </component>\n  </settings>\n");
+
+    xml.push_str("  <settings pass=\"oobeSystem\">\n");
+    let _ = writeln!(xml, "    {}", component("Microsoft-Windows-International-Core"));
+    push_locales(&mut xml, locale, input_locale);
+    xml.push_str("    </component>\n");
+    let _ = writeln!(xml, "    {}", component("Microsoft-Windows-Shell-Setup"));
+    let password = options.password.as_deref().map(escape_xml).unwrap_or_default();
+    let username = escape_xml(&options.username);
+    if options.winrm {
+        push_winrm(&mut xml, &username, &password);
+    }
+    xml.push_str(
+        "      <OOBE>\n        <HideEULAPage>true</HideEULAPage>\n        <HideOnlineAccountScreens>true</HideOnlineAccountScreens>\n        <ProtectYourPC>3</ProtectYourPC>\n      </OOBE>\n",
+    );
+    let is_server = entry.release.family() == ReleaseFamily::Server;
+    let group = if is_server { "Administrators" } else { "Administrators;Users" };
+    xml.push_str("      <UserAccounts>\n");
+    if is_server && options.password.is_some() {
+        let _ = writeln!(
+            xml,
+            "        <AdministratorPassword>\n          <Value>{password}</Value>\n          <PlainText>true</PlainText>\n        </AdministratorPassword>"
+        );
+    }
+    let _ = writeln!(
+        xml,
+        "        <LocalAccounts>\n          <LocalAccount wcm:action=\"add\">\n            <Name>{username}</Name>\n            <Group>{group}</Group>\n            <Password>\n              <Value>{password}</Value>\n              <PlainText>true</PlainText>\n            </Password>\n          </LocalAccount>\n        </LocalAccounts>\n      </UserAccounts>"
+    );
+    xml.push_str("    </component>\n  </settings>\n</unattend>\n");
+    xml
+}
+
+fn push_locales(xml: &mut String, locale: &str, input_locale: &str) {
+    let _ = writeln!(xml, "      <InputLocale>{input_locale}</InputLocale>");
+    let _ = writeln!(xml, "      <SystemLocale>{locale}</SystemLocale>");
+    let _ = writeln!(xml, "      <UILanguage>{locale}</UILanguage>");
+    let _ = writeln!(xml, "      <UserLocale>{locale}</UserLocale>");
+}
+
+fn push_winrm(xml: &mut String, username: &str, password: &str) {
+    // First logon commands only run once someone logs in
+    let _ = writeln!(
+        xml,
+        "      <AutoLogon>\n        <Enabled>true</Enabled>\n        <LogonCount>1</LogonCount>\n        <Username>{username}</Username>\n        <Password>\n          <Value>{password}</Value>\n          <PlainText>true</PlainText>\n        </Password>\n      </AutoLogon>"
+    );
+    let commands = [
+        // WinRM refuses to listen while any network is public
+        "powershell -Command \"Get-NetConnectionProfile | Set-NetConnectionProfile -NetworkCategory Private\"",
+        "winrm quickconfig -quiet",
+        "winrm set winrm/config/service @{AllowUnencrypted=\"true\"}",
+        "winrm set winrm/config/service/auth @{Basic=\"true\"}",
+        "netsh advfirewall firewall add rule name=\"WinRM HTTP\" dir=in action=allow protocol=TCP localport=5985",
+    ];
+    xml.push_str("      <FirstLogonCommands>\n");
+    for (order, command) in commands.iter().enumerate() {
+        let _ = writeln!(
+            xml,
+            "        <SynchronousCommand wcm:action=\"add\">\n          <Order>{}</Order>\n          <CommandLine>cmd /c {}</CommandLine>\n        </SynchronousCommand>",
+            order + 1,
+            escape_xml(command)
+        );
+    }
+    xml.push_str("      </FirstLogonCommands>\n");
+}
+
+struct Partition {
+    kind: &'static str,
+    /// Size in MB, or None to fill the rest of the disk
+    size: Option<u32>,
+    format: Option<&'static str>,
+    label: &'static str,
+    letter: Option<char>,
+    active: bool,
+}
+
+fn push_disk_configuration(xml: &mut String, layout: DiskLayout) {
+    let partition = |kind, size, format, label, letter, active| Partition {
+        kind,
+        size,
+        format,
+        label,
+        letter,
+        active,
+    };
+    let partitions = match layout {
+        DiskLayout::Uefi => vec![
+            partition("EFI", Some(100), Some("FAT32"), "System", None, false),
+            // The MSR partition must not be formatted
+            partition("MSR", Some(16), None, "", None, false),
+            partition("Primary", None, Some("NTFS"), "Windows", Some('C'), false),
+        ],
+        DiskLayout::Bios => vec![
+            partition("Primary", Some(100), Some("NTFS"), "System Reserved", None, true),
+            partition("Primary", None, Some("NTFS"), "Windows", Some('C'), false),
+        ],
+        DiskLayout::Manual => return,
+    };
+
+    xml.push_str("      <DiskConfiguration>\n        <Disk wcm:action=\"add\">\n          <DiskID>0</DiskID>\n          <WillWipeDisk>true</WillWipeDisk>\n");
+    xml.push_str("          <CreatePartitions>\n");
+    for (order, partition) in partitions.iter().enumerate() {
+        let size = match partition.size {
+            Some(size) => format!("<Size>{size}</Size>"),
+            None => "<Extend>true</Extend>".to_string(),
+        };
+        let _ = writeln!(
+            xml,
+            "            <CreatePartition wcm:action=\"add\">\n              <Order>{}</Order>\n              <Type>{}</Type>\n              {size}\n            </CreatePartition>",
+            order + 1,
+            partition.kind
+        );
+    }
+    xml.push_str("          </CreatePartitions>\n          <ModifyPartitions>\n");
+    for (order, partition) in partitions.iter().enumerate() {
+        let Some(format) = partition.format else { continue };
+        let _ = write!(
+            xml,
+            "            <ModifyPartition wcm:action=\"add\">\n              <Order>{0}</Order>\n              <PartitionID>{0}</PartitionID>\n              <Format>{format}</Format>\n              <Label>{1}</Label>\n",
+            order + 1,
+            partition.label
+        );
+        if let Some(letter) = partition.letter {
+            let _ = writeln!(xml, "              <Letter>{letter}</Letter>");
+        }
+        if partition.active {
+            xml.push_str("              <Active>true</Active>\n");
+        }
+        xml.push_str("            </ModifyPartition>\n");
+    }
+    xml.push_str("          </ModifyPartitions>\n        </Disk>\n      </DiskConfiguration>\n");
+}
+
+/// The default keyboard layout for a locale, formatted as '<language ID>:<keyboard layout ID>'
+fn input_locale(locale: &str) -> &'static str {
+    match locale {
+        "ar-SA" => "0401:00000401",
+        "bg-BG" => "0402:00030402",
+        "cs-CZ" => "0405:00000405",
+        "da-DK" => "0406:00000406",
+        "de-DE" => "0407:00000407",
+        "el-GR" => "0408:00000408",
+        "en-GB" => "0809:00000809",
+        "en-US" => "0409:00000409",
+        "es-ES" => "0c0a:0000040a",
+        "es-MX" => "080a:0000080a",
+        "et-EE" => "0425:00000425",
+        "fi-FI" => "040b:0000040b",
+        "fr-CA" => "0c0c:00001009",
+        "fr-FR" => "040c:0000040c",
+        "he-IL" => "040d:0002040d",
+        "hr-HR" => "041a:0000041a",
+        "hu-HU" => "040e:0000040e",
+        "it-IT" => "0410:00000410",
+        "ja-JP" => "0411:00000411",
+        "ko-KR" => "0412:00000412",
+        "lt-LT" => "0427:00010427",
+        "lv-LV" => "0426:00020426",
+        "nb-NO" => "0414:00000414",
+        "nl-NL" => "0413:00020409",
+        "pl-PL" => "0415:00000415",
+        "pt-BR" => "0416:00000416",
+        "pt-PT" => "0816:00000816",
+        "ro-RO" => "0418:00010418",
+        "ru-RU" => "0419:00000419",
+        "sk-SK" => "041b:0000041b",
+        "sl-SI" => "0424:00000424",
+        "sr-Latn-RS" => "241a:0000081a",
+        "sv-SE" => "041d:0000041d",
+        "th-TH" => "041e:0000041e",
+        "tr-TR" => "041f:0000041f",
+        "uk-UA" => "0422:00020422",
+        "zh-CN" => "0804:00000804",
+        "zh-TW" => "0404:00000404",
+        _ => "0409:00000409",
+    }
+}
+
+#[cfg(all(test, feature = "enterprise"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_administrator_password() {
+        let options = UnattendOptions {
+            password: Some("P@ssw0rd<1>".to_string()),
+            ..Default::default()
+        };
+        let server = autounattend(&"enterprise/server-2022@en-us/x86_64".parse().unwrap(), &options);
+        assert!(server.contains("<AdministratorPassword>\n          <Value>P@ssw0rd&lt;1&gt;</Value>"));
+        assert!(server.contains("<Group>Administrators</Group>"));
+
+        let client = autounattend(&"enterprise/11-enterprise@en-us/x86_64".parse().unwrap(), &options);
+        assert!(!client.contains("<AdministratorPassword>"));
+    }
+
+    #[test]
+    fn winrm() {
+        let entry = "enterprise/11-enterprise@en-us/x86_64".parse().unwrap();
+        assert!(!autounattend(&entry, &UnattendOptions::default()).contains("winrm"));
+
+        let options = UnattendOptions {
+            password: Some("vagrant".to_string()),
+            winrm: true,
+            ..Default::default()
+        };
+        let xml = autounattend(&entry, &options);
+        assert!(xml.contains("<AutoLogon>\n        <Enabled>true</Enabled>\n        <LogonCount>1</LogonCount>\n        <Username>User</Username>"));
+        assert!(xml.contains("<CommandLine>cmd /c winrm quickconfig -quiet</CommandLine>"));
+        assert!(xml.contains("<CommandLine>cmd /c winrm set winrm/config/service/auth @{Basic=&quot;true&quot;}</CommandLine>"));
+        assert!(xml.contains("localport=5985"));
+        roxmltree::Document::parse(&xml).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+use crate::ImageBuild;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The feature update and build of a Windows image, as far as they could be determined.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowsVersion {
+    /// Feature update tag, e.g. '24H2'
+    pub feature_update: Option<String>,
+    pub build: Option<u32>,
+    pub revision: Option<u32>,
+}
+
+impl WindowsVersion {
+    /// Gather whatever version information is present in a product name, label or filename,
+    /// e.g. 'Windows 11 24H2', 'Win11_24H2_English_x64.iso', or
+    /// '26100.1742.240906-0331.ge_release_svc_refresh_CLIENT_CONSUMER_x64FRE_en-us.iso'
+    pub fn from_text(text: &str) -> Self {
+        let mut version = Self {
+            feature_update: find_feature_update(text),
+            ..Default::default()
+        };
+        if let Some(build) = ImageBuild::from_filename(text) {
+            version.set_build(&build);
+        }
+        version
+    }
+
+    fn set_build(&mut self, build: &ImageBuild) {
+        self.build = Some(build.build);
+        self.revision = Some(build.revision);
+        if self.feature_update.is_none() {
+            self.feature_update = feature_update_for_build(build.build).map(str::to_string);
+        }
+    }
+
+    /// Fill in any fields that are missing from `other`
+    pub(crate) fn merge(&mut self, other: Self) {
+        self.feature_update = self.feature_update.take().or(other.feature_update);
+        self.build = self.build.or(other.build);
+        self.revision = self.revision.or(other.revision);
+        if let (None, Some(build)) = (&self.feature_update, self.build) {
+            self.feature_update = feature_update_for_build(build).map(str::to_string);
+        }
+    }
+}
+
+impl fmt::Display for WindowsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.feature_update, self.build, self.revision) {
+            (Some(tag), Some(build), Some(revision)) => write!(f, "{tag} ({build}.{revision})"),
+            (Some(tag), Some(build), None) => write!(f, "{tag} ({build})"),
+            (None, Some(build), Some(revision)) => write!(f, "{build}.{revision}"),
+            (None, Some(build), None) => write!(f, "{build}"),
+            (Some(tag), None, _) => write!(f, "{tag}"),
+            (None, None, _) => write!(f, "Unknown"),
+        }
+    }
+}
+
+fn find_feature_update(text: &str) -> Option<String> {
+    // Feature updates are tagged as '<yy>H<half>', e.g. '23H2'
+    text.split(|c: char| !c.is_ascii_alphanumeric())
+        .flat_map(|token| token.split('_'))
+        .find(|token| {
+            let bytes = token.as_bytes();
+            bytes.len() == 4 && bytes[..2].iter().all(u8::is_ascii_digit) && bytes[2].eq_ignore_ascii_case(&b'h') && matches!(bytes[3], b'1' | b'2')
+        })
+        .map(str::to_ascii_uppercase)
+}
+
+fn feature_update_for_build(build: u32) -> Option<&'static str> {
+    Some(match build {
+        26100 => "24H2",
+        22631 => "23H2",
+        22621 => "22H2",
+        22000 => "21H2",
+        20348 => "21H2",
+        19045 => "22H2",
+        19044 => "21H2",
+        17763 => "1809",
+        14393 => "1607",
+        _ => return None,
+    })
+}
@@ -0,0 +1,519 @@
+//! Virtual machine configuration for testing images, with the firmware, TPM and disk requirements of each release.
+
+use crate::{export::escape_xml, WindowsArchitecture, WindowsData, WindowsEntry, WindowsRelease};
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+/// Red Hat's signed VirtIO drivers for Windows guests
+pub const VIRTIO_WIN_URL: &str = "https://fedorapeople.org/groups/virt/virtio-win/direct-downloads/stable-virtio/virtio-win.iso";
+
+const OVMF_CODE: &str = "/usr/share/OVMF/OVMF_CODE_4M.fd";
+const OVMF_CODE_SECURE_BOOT: &str = "/usr/share/OVMF/OVMF_CODE_4M.secboot.fd";
+const OVMF_VARS: &str = "/usr/share/OVMF/OVMF_VARS_4M.fd";
+// Variables with Microsoft's keys enrolled, which secure boot needs to start Windows
+const OVMF_VARS_SECURE_BOOT: &str = "/usr/share/OVMF/OVMF_VARS_4M.ms.fd";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Firmware {
+    Uefi,
+    Bios,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Machine {
+    Q35,
+    I440fx,
+}
+
+/// The Packer builders which a source block can be generated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackerBuilder {
+    Qemu,
+    VirtualBox,
+    HyperV,
+}
+
+impl PackerBuilder {
+    /// The builder's name in a `source` block
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Qemu => "qemu",
+            Self::VirtualBox => "virtualbox-iso",
+            Self::HyperV => "hyperv-iso",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmConfig {
+    pub name: String,
+    pub release: WindowsRelease,
+    pub arch: WindowsArchitecture,
+    pub iso: PathBuf,
+    pub disk_image: PathBuf,
+    pub disk_size_gb: u32,
+    pub memory_mb: u32,
+    pub cpus: u32,
+    pub firmware: Firmware,
+    pub secure_boot: bool,
+    pub tpm: bool,
+    pub machine: Machine,
+    /// A VirtIO driver ISO to attach alongside the installer, see [`VIRTIO_WIN_URL`]. Only [`Self::qemu_command`]
+    /// downloads it if it's missing.
+    pub driver_iso: Option<PathBuf>,
+    /// An Autounattend.xml, attached to Packer builds on a CD so that setup runs unattended
+    pub answer_file: PathBuf,
+}
+
+impl VmConfig {
+    /// Configuration for installing the given entry from a local ISO, with the release's requirements applied
+    pub fn new(entry: &WindowsEntry, iso: impl Into<PathBuf>) -> Self {
+        let iso = iso.into();
+        let directory = iso.parent().map(Path::to_path_buf).unwrap_or_default();
+        let name = format!("{}-{}", entry.release, entry.arch)
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("-")
+            .to_ascii_lowercase();
+
+        let needs_tpm = requires_tpm(entry.release);
+        let legacy = entry.arch == WindowsArchitecture::i686;
+        Self {
+            disk_image: directory.join(format!("{name}.qcow2")),
+            driver_iso: None,
+            answer_file: directory.join("Autounattend.xml"),
+            name,
+            release: entry.release,
+            arch: entry.arch,
+            iso,
+            disk_size_gb: 64,
+            memory_mb: if legacy { 2048 } else { 4096 },
+            cpus: 2,
+            // 32-bit Windows doesn't support UEFI on x86_64 firmware
+            firmware: if legacy { Firmware::Bios } else { Firmware::Uefi },
+            secure_boot: needs_tpm,
+            tpm: needs_tpm,
+            machine: if legacy { Machine::I440fx } else { Machine::Q35 },
+        }
+    }
+
+    /// Configuration for a resolved image, which is expected to be downloaded to `directory` under its own filename
+    pub fn from_data(data: &WindowsData, directory: impl AsRef<Path>) -> Self {
+        Self::new(&data.info, directory.as_ref().join(data.filename()))
+    }
+
+    /// A quickemu `.conf` file
+    pub fn to_quickemu(&self) -> String {
+        let on_off = |value| if value { "on" } else { "off" };
+        let mut conf = String::from("#!/usr/bin/quickemu --vm\nguest_os=\"windows\"\n");
+        let _ = writeln!(conf, "disk_img=\"{}\"", self.disk_image.display());
+        let _ = writeln!(conf, "disk_size=\"{}G\"", self.disk_size_gb);
+        let _ = writeln!(conf, "iso=\"{}\"", self.iso.display());
+        match &self.driver_iso {
+            Some(driver_iso) => {
+                let _ = writeln!(conf, "# VirtIO drivers can be downloaded from {VIRTIO_WIN_URL}");
+                let _ = writeln!(conf, "fixed_iso=\"{}\"", driver_iso.display());
+            }
+            None => {
+                let _ = writeln!(
+                    conf,
+                    "# VirtIO drivers can be downloaded from {VIRTIO_WIN_URL} and attached with fixed_iso"
+                );
+            }
+        }
+        let _ = writeln!(conf, "ram=\"{}G\"", self.memory_mb.div_ceil(1024));
+        let _ = writeln!(conf, "cpu_cores=\"{}\"", self.cpus);
+        if self.firmware == Firmware::Bios {
+            conf.push_str("boot=\"legacy\"\n");
+        }
+        let _ = writeln!(conf, "tpm=\"{}\"", on_off(self.tpm));
+        let _ = writeln!(conf, "secureboot=\"{}\"", on_off(self.secure_boot));
+        conf
+    }
+
+    /// Arguments for `qemu-system-x86_64`. A TPM requires swtpm to be listening on a socket next to the disk image
+    /// (as started by [`Self::qemu_command`]), and UEFI firmware uses OVMF from its usual location.
+    pub fn qemu_args(&self) -> Vec<String> {
+        let mut args = vec!["-name".to_string(), self.name.clone()];
+        let machine = match self.machine {
+            // Secure boot requires SMM, so that the firmware's variables can't be modified by the guest
+            Machine::Q35 if self.secure_boot => "q35,smm=on,accel=kvm",
+            Machine::Q35 => "q35,accel=kvm",
+            Machine::I440fx => "pc,accel=kvm",
+        };
+        args.extend(["-machine".into(), machine.into()]);
+        // Hyper-V enlightenments significantly improve Windows guest performance
+        args.extend(["-cpu".into(), "host,hv_relaxed,hv_spinlocks=0x1fff,hv_vapic,hv_time".into()]);
+        args.extend(["-smp".into(), self.cpus.to_string(), "-m".into(), self.memory_mb.to_string()]);
+
+        if self.firmware == Firmware::Uefi {
+            let code = if self.secure_boot { OVMF_CODE_SECURE_BOOT } else { OVMF_CODE };
+            if self.secure_boot {
+                args.extend(["-global".into(), "driver=cfi.pflash01,property=secure,value=on".into()]);
+            }
+            args.extend(["-drive".into(), format!("if=pflash,format=raw,unit=0,readonly=on,file={code}")]);
+            args.extend(["-drive".into(), format!("if=pflash,format=raw,unit=1,file={}", self.vars_path().display())]);
+        }
+        if self.tpm {
+            args.extend(["-chardev".into(), format!("socket,id=chrtpm,path={}", self.tpm_socket_path().display())]);
+            args.extend(["-tpmdev".into(), "emulator,id=tpm0,chardev=chrtpm".into()]);
+            args.extend(["-device".into(), "tpm-tis,tpmdev=tpm0".into()]);
+        }
+
+        // Windows setup has no VirtIO drivers, so the disk is attached over SATA/IDE
+        args.extend(["-drive".into(), format!("file={},format=qcow2,if=ide,index=0", self.disk_image.display())]);
+        args.extend(["-drive".into(), format!("file={},media=cdrom,index=1", self.iso.display())]);
+        if let Some(driver_iso) = &self.driver_iso {
+            args.extend(["-drive".into(), format!("file={},media=cdrom,index=2", driver_iso.display())]);
+        }
+        args.extend(["-boot".into(), "order=d".into(), "-device".into(), "usb-ehci".into(), "-device".into(), "usb-tablet".into()]);
+        args
+    }
+
+    /// A shell command line which creates the disk image and downloads the driver ISO if needed, starts swtpm if the VM
+    /// has a TPM, and starts QEMU
+    pub fn qemu_command(&self) -> String {
+        let mut command = format!(
+            "[ -f {0} ] || qemu-img create -f qcow2 {0} {1}G\n",
+            shell_quote(&self.disk_image.display().to_string()),
+            self.disk_size_gb
+        );
+        if let Some(driver_iso) = &self.driver_iso {
+            let driver_iso = shell_quote(&driver_iso.display().to_string());
+            let _ = writeln!(command, "[ -f {driver_iso} ] || curl -Lo {driver_iso} {VIRTIO_WIN_URL}");
+        }
+        if self.firmware == Firmware::Uefi {
+            let vars = shell_quote(&self.vars_path().display().to_string());
+            let template = if self.secure_boot { OVMF_VARS_SECURE_BOOT } else { OVMF_VARS };
+            let _ = writeln!(command, "[ -f {vars} ] || cp {template} {vars}");
+        }
+        if self.tpm {
+            // The TPM's state persists across runs, and swtpm exits along with QEMU
+            let state = shell_quote(&self.disk_image.with_extension("tpm").display().to_string());
+            let socket = shell_quote(&self.tpm_socket_path().display().to_string());
+            let _ = writeln!(command, "mkdir -p {state}");
+            let _ = writeln!(
+                command,
+                "swtpm socket --tpm2 --tpmstate dir={state} --ctrl type=unixio,path={socket} --terminate --daemon"
+            );
+        }
+        command.push_str("qemu-system-x86_64");
+        for arg in self.qemu_args() {
+            command.push(' ');
+            command.push_str(&shell_quote(&arg));
+        }
+        command.push('\n');
+        command
+    }
+
+    /// A libvirt domain definition, suitable for `virsh define`. The disk image isn't created by libvirt, so it
+    /// must exist beforehand (e.g. `qemu-img create -f qcow2 <disk> 64G`), as must the driver ISO if one is set.
+    pub fn to_libvirt(&self) -> String {
+        let yes_no = |value| if value { "yes" } else { "no" };
+        let mut xml = String::from("<domain type=\"kvm\">\n");
+        let _ = writeln!(xml, "  <name>{}</name>", escape_xml(&self.name));
+        let _ = writeln!(xml, "  <memory unit=\"MiB\">{}</memory>", self.memory_mb);
+        let _ = writeln!(xml, "  <vcpu>{}</vcpu>", self.cpus);
+
+        let (arch, machine) = match (self.arch, self.machine) {
+            (WindowsArchitecture::i686, Machine::I440fx) => ("i686", "pc"),
+            (WindowsArchitecture::i686, Machine::Q35) => ("i686", "q35"),
+            (WindowsArchitecture::x86_64, Machine::I440fx) => ("x86_64", "pc"),
+            (WindowsArchitecture::x86_64, Machine::Q35) => ("x86_64", "q35"),
+        };
+        match self.firmware {
+            Firmware::Uefi => {
+                xml.push_str("  <os firmware=\"efi\">\n");
+                let _ = writeln!(xml, "    <type arch=\"{arch}\" machine=\"{machine}\">hvm</type>");
+                xml.push_str("    <firmware>\n");
+                let _ = writeln!(
+                    xml,
+                    "      <feature enabled=\"{}\" name=\"secure-boot\"/>",
+                    yes_no(self.secure_boot)
+                );
+                let _ = writeln!(
+                    xml,
+                    "      <feature enabled=\"{}\" name=\"enrolled-keys\"/>",
+                    yes_no(self.secure_boot)
+                );
+                xml.push_str("    </firmware>\n");
+            }
+            Firmware::Bios => {
+                xml.push_str("  <os>\n");
+                let _ = writeln!(xml, "    <type arch=\"{arch}\" machine=\"{machine}\">hvm</type>");
+            }
+        }
+        xml.push_str("    <boot dev=\"cdrom\"/>\n    <boot dev=\"hd\"/>\n  </os>\n");
+
+        xml.push_str("  <features>\n    <acpi/>\n    <apic/>\n");
+        xml.push_str("    <hyperv mode=\"custom\">\n");
+        xml.push_str("      <relaxed state=\"on\"/>\n      <vapic state=\"on\"/>\n      <spinlocks state=\"on\" retries=\"8191\"/>\n");
+        xml.push_str("    </hyperv>\n");
+        if self.secure_boot {
+            xml.push_str("    <smm state=\"on\"/>\n");
+        }
+        xml.push_str("  </features>\n  <cpu mode=\"host-passthrough\"/>\n");
+        xml.push_str("  <clock offset=\"localtime\">\n    <timer name=\"hypervclock\" present=\"yes\"/>\n  </clock>\n");
+
+        // i440fx has no SATA controller, and Windows setup has no VirtIO drivers
+        let (bus, prefix) = match self.machine {
+            Machine::Q35 => ("sata", "sd"),
+            Machine::I440fx => ("ide", "hd"),
+        };
+        xml.push_str("  <devices>\n");
+        let _ = writeln!(xml, "    <disk type=\"file\" device=\"disk\">");
+        xml.push_str("      <driver name=\"qemu\" type=\"qcow2\"/>\n");
+        let _ = writeln!(
+            xml,
+            "      <source file=\"{}\"/>",
+            escape_xml(&self.disk_image.display().to_string())
+        );
+        let _ = writeln!(xml, "      <target dev=\"{prefix}a\" bus=\"{bus}\"/>\n    </disk>");
+        let cdroms = std::iter::once(&self.iso).chain(&self.driver_iso);
+        for (cdrom, dev) in cdroms.zip(['b', 'c']) {
+            xml.push_str("    <disk type=\"file\" device=\"cdrom\">\n      <driver name=\"qemu\" type=\"raw\"/>\n");
+            let _ = writeln!(xml, "      <source file=\"{}\"/>", escape_xml(&cdrom.display().to_string()));
+            let _ = writeln!(
+                xml,
+                "      <target dev=\"{prefix}{dev}\" bus=\"{bus}\"/>\n      <readonly/>\n    </disk>"
+            );
+        }
+        if self.tpm {
+            xml.push_str("    <tpm model=\"tpm-crb\">\n      <backend type=\"emulator\" version=\"2.0\"/>\n    </tpm>\n");
+        }
+        let nic = if self.machine == Machine::Q35 { "e1000e" } else { "e1000" };
+        let _ = writeln!(
+            xml,
+            "    <interface type=\"network\">\n      <source network=\"default\"/>\n      <model type=\"{nic}\"/>\n    </interface>"
+        );
+        xml.push_str("    <input type=\"tablet\" bus=\"usb\"/>\n");
+        xml.push_str("    <graphics type=\"spice\" autoport=\"yes\"/>\n");
+        xml.push_str("    <video>\n      <model type=\"qxl\"/>\n    </video>\n");
+        xml.push_str("  </devices>\n</domain>\n");
+        xml
+    }
+
+    /// A Packer HCL `source` block which installs from `iso_url`, with the answer file attached on a CD. WinRM is used as
+    /// the communicator, so the answer file must be generated with [`crate::unattend::UnattendOptions::winrm`] set, for the
+    /// default account; its password is left to a `winrm_password` variable.
+    pub fn to_packer(&self, builder: PackerBuilder, iso_url: &str, iso_checksum: Option<&str>) -> String {
+        let mut hcl = format!("source \"{}\" \"{}\" {{\n", builder.name(), self.name);
+        let mut set = |key: &str, value: String| {
+            let _ = writeln!(hcl, "  {key} = {value}");
+        };
+        set("iso_url", hcl_string(iso_url));
+        set(
+            "iso_checksum",
+            hcl_string(&iso_checksum.map_or("none".to_string(), |hash| format!("sha256:{}", hash.to_ascii_lowercase()))),
+        );
+        set("cpus", self.cpus.to_string());
+        set("memory", self.memory_mb.to_string());
+
+        match builder {
+            PackerBuilder::Qemu => {
+                set("disk_size", hcl_string(&format!("{}G", self.disk_size_gb)));
+                set("accelerator", hcl_string("kvm"));
+                set(
+                    "machine_type",
+                    hcl_string(if self.machine == Machine::Q35 { "q35" } else { "pc" }),
+                );
+                set("disk_interface", hcl_string("ide"));
+                if self.firmware == Firmware::Uefi {
+                    set("efi_boot", "true".into());
+                    let (code, vars) = if self.secure_boot {
+                        (OVMF_CODE_SECURE_BOOT, OVMF_VARS_SECURE_BOOT)
+                    } else {
+                        (OVMF_CODE, OVMF_VARS)
+                    };
+                    set("efi_firmware_code", hcl_string(code));
+                    set("efi_firmware_vars", hcl_string(vars));
+                }
+                if self.secure_boot {
+                    set(
+                        "qemuargs",
+                        "[[\"-machine\", \"q35,smm=on,accel=kvm\"], [\"-global\", \"driver=cfi.pflash01,property=secure,value=on\"]]".into(),
+                    );
+                }
+                if self.tpm {
+                    set("vtpm", "true".into());
+                }
+            }
+            PackerBuilder::VirtualBox => {
+                set("guest_os_type", hcl_string(virtualbox_os_type(self.release, self.arch)));
+                set("disk_size", (self.disk_size_gb * 1024).to_string());
+                set(
+                    "firmware",
+                    hcl_string(if self.firmware == Firmware::Uefi { "efi" } else { "bios" }),
+                );
+                set(
+                    "chipset",
+                    hcl_string(if self.machine == Machine::Q35 { "ich9" } else { "piix3" }),
+                );
+                set("hard_drive_interface", hcl_string("sata"));
+                set("iso_interface", hcl_string("sata"));
+                let mut vboxmanage = Vec::new();
+                if self.tpm {
+                    vboxmanage.push("[\"modifyvm\", \"{{.Name}}\", \"--tpm-type\", \"2.0\"]");
+                }
+                if self.secure_boot {
+                    vboxmanage.push("[\"modifynvram\", \"{{.Name}}\", \"inituefivarstore\"]");
+                    vboxmanage.push("[\"modifynvram\", \"{{.Name}}\", \"enrollmssignatures\"]");
+                    vboxmanage.push("[\"modifynvram\", \"{{.Name}}\", \"enrollorclpk\"]");
+                    vboxmanage.push("[\"modifynvram\", \"{{.Name}}\", \"secureboot\", \"--enable\"]");
+                }
+                if !vboxmanage.is_empty() {
+                    set("vboxmanage", format!("[{}]", vboxmanage.join(", ")));
+                }
+            }
+            PackerBuilder::HyperV => {
+                set("disk_size", (self.disk_size_gb * 1024).to_string());
+                // Generation 2 VMs are UEFI only, and can't boot 32-bit Windows
+                set("generation", if self.firmware == Firmware::Uefi { "2" } else { "1" }.into());
+                if self.firmware == Firmware::Uefi {
+                    set("enable_secure_boot", self.secure_boot.to_string());
+                    if self.secure_boot {
+                        set("secure_boot_template", hcl_string("MicrosoftWindows"));
+                    }
+                }
+                if self.tpm {
+                    set("enable_tpm", "true".into());
+                }
+                set("switch_name", hcl_string("Default Switch"));
+            }
+        }
+
+        // Setup searches the root of every drive for Autounattend.xml
+        set("cd_files", format!("[{}]", hcl_string(&self.answer_file.display().to_string())));
+        // UEFI setup media only boots if a key is pressed
+        set("boot_wait", hcl_string("2s"));
+        set("boot_command", "[\"<spacebar><wait><spacebar>\"]".into());
+        set("communicator", hcl_string("winrm"));
+        set(
+            "winrm_username",
+            hcl_string(&crate::unattend::UnattendOptions::default().username),
+        );
+        set("winrm_password", "var.winrm_password".into());
+        set("winrm_timeout", hcl_string("2h"));
+        set("shutdown_command", hcl_string("shutdown /s /t 10 /f"));
+        hcl.push_str("}\n");
+        hcl
+    }
+
+    fn vars_path(&self) -> PathBuf {
+        self.disk_image.with_extension("ovmf_vars.fd")
+    }
+
+    fn tpm_socket_path(&self) -> PathBuf {
+        self.disk_image.with_extension("swtpm-sock")
+    }
+}
+
+fn virtualbox_os_type(release: WindowsRelease, arch: WindowsArchitecture) -> &'static str {
+    match release {
+        #[cfg(feature = "consumer")]
+        WindowsRelease::Consumer(crate::ConsumerRelease::Eleven) => return "Windows11_64",
+        #[cfg(feature = "consumer")]
+        WindowsRelease::Consumer(release) if release.base_build() == Some(9600) => {
+            return if arch == WindowsArchitecture::i686 { "Windows81" } else { "Windows81_64" };
+        }
+        #[cfg(feature = "enterprise")]
+        WindowsRelease::Enterprise(release) => match release {
+            crate::EnterpriseRelease::ElevenEnterprise => return "Windows11_64",
+            crate::EnterpriseRelease::Server2022 => return "Windows2022_64",
+            crate::EnterpriseRelease::Server2019 => return "Windows2019_64",
+            crate::EnterpriseRelease::Server2016 => return "Windows2016_64",
+            crate::EnterpriseRelease::Server2012R2 => return "Windows2012_64",
+            crate::EnterpriseRelease::TenEnterprise | crate::EnterpriseRelease::TenLtsc => {}
+        },
+        #[allow(unreachable_patterns)]
+        _ => {}
+    }
+    match arch {
+        WindowsArchitecture::x86_64 => "Windows10_64",
+        WindowsArchitecture::i686 => "Windows10",
+    }
+}
+
+fn requires_tpm(release: WindowsRelease) -> bool {
+    match release {
+        #[cfg(feature = "consumer")]
+        // Custom product IDs are treated like Windows 10, as they are by virtualbox_os_type
+        WindowsRelease::Consumer(release) => release == crate::ConsumerRelease::Eleven,
+        #[cfg(feature = "enterprise")]
+        WindowsRelease::Enterprise(release) => {
+            matches!(
+                release,
+                crate::EnterpriseRelease::ElevenEnterprise | crate::EnterpriseRelease::Server2022
+            )
+        }
+    }
+}
+
+fn hcl_string(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace("${", "$${")
+        .replace("%{", "%%{");
+    format!("\"{escaped}\"")
+}
+
+fn shell_quote(arg: &str) -> String {
+    if arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=,:".contains(c)) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(all(test, feature = "consumer"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn driver_iso_is_downloaded_when_attached() {
+        let entry = WindowsEntry::new("11", "English (United States)", "x86_64").unwrap();
+        let mut config = VmConfig::new(&entry, "/vms/Win11_24H2_English_x64.iso");
+        assert_eq!(config.driver_iso, None);
+        assert!(!config.qemu_command().contains("virtio-win"));
+        assert!(!config.to_libvirt().contains("virtio-win"));
+
+        config.driver_iso = Some(PathBuf::from("/vms/virtio-win.iso"));
+        let command = config.qemu_command();
+        assert!(command.contains(&format!(
+            "[ -f /vms/virtio-win.iso ] || curl -Lo /vms/virtio-win.iso {VIRTIO_WIN_URL}\n"
+        )));
+        assert!(command.contains("file=/vms/virtio-win.iso,media=cdrom,index=2"));
+    }
+
+    #[test]
+    fn tpm_is_started_next_to_disk() {
+        let entry = WindowsEntry::new("11", "English (United States)", "x86_64").unwrap();
+        let command = VmConfig::new(&entry, "/vms/Win11_24H2_English_x64.iso").qemu_command();
+        let swtpm = command
+            .find("swtpm socket --tpm2 --tpmstate dir=/vms/windows-11-x86-64.tpm --ctrl type=unixio,path=/vms/windows-11-x86-64.swtpm-sock")
+            .unwrap();
+        assert!(command.contains("mkdir -p /vms/windows-11-x86-64.tpm\n"));
+        assert!(swtpm < command.find("qemu-system-x86_64").unwrap());
+        assert!(command.contains("socket,id=chrtpm,path=/vms/windows-11-x86-64.swtpm-sock"));
+
+        let custom = WindowsEntry::new("productid:3113", "English (United States)", "x86_64").unwrap();
+        let config = VmConfig::new(&custom, "/vms/custom.iso");
+        assert!(!config.tpm);
+        assert!(!config.qemu_command().contains("swtpm"));
+    }
+
+    #[test]
+    fn packer_attaches_answer_file() {
+        let entry = WindowsEntry::new("11", "English (United States)", "x86_64").unwrap();
+        let config = VmConfig::new(&entry, "/vms/Win11_24H2_English_x64.iso");
+        for builder in [PackerBuilder::Qemu, PackerBuilder::VirtualBox, PackerBuilder::HyperV] {
+            let hcl = config.to_packer(builder, "https://example.com/Win11.iso", None);
+            assert!(hcl.contains("  cd_files = [\"/vms/Autounattend.xml\"]\n"));
+            assert!(hcl.contains("  winrm_username = \"User\"\n"));
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::{Catalog, CatalogDiff, ProviderRegistry, RidoError, WindowsEntry};
+use reqwest::{header::CONTENT_TYPE, Url};
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+/// Periodically re-resolves a set of entries, and notifies its hooks whenever the build or hash of one changes.
+pub struct Watcher {
+    registry: ProviderRegistry,
+    entries: Vec<WindowsEntry>,
+    interval: Duration,
+    resolve_remote: bool,
+    hooks: Vec<WatchHook>,
+    last: Option<Catalog>,
+}
+
+enum WatchHook {
+    Callback(Box<dyn FnMut(&CatalogDiff) + Send>),
+    /// The diff is POSTed as JSON
+    Webhook(Url),
+    /// The diff is written to the command's stdin as JSON
+    Command(String, Vec<String>),
+}
+
+impl Watcher {
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+    pub fn new(registry: ProviderRegistry, entries: Vec<WindowsEntry>) -> Self {
+        Self {
+            registry,
+            entries,
+            interval: Self::DEFAULT_INTERVAL,
+            resolve_remote: false,
+            hooks: Vec::new(),
+            last: None,
+        }
+    }
+    pub fn entries(mut self, entries: Vec<WindowsEntry>) -> Self {
+        self.entries = entries;
+        self
+    }
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+    /// Follow redirects on each check, so that enterprise builds can be compared
+    pub fn resolve_remote(mut self, resolve_remote: bool) -> Self {
+        self.resolve_remote = resolve_remote;
+        self
+    }
+    pub fn on_change(mut self, callback: impl FnMut(&CatalogDiff) + Send + 'static) -> Self {
+        self.hooks.push(WatchHook::Callback(Box::new(callback)));
+        self
+    }
+    pub fn webhook(mut self, url: Url) -> Self {
+        self.hooks.push(WatchHook::Webhook(url));
+        self
+    }
+    pub fn command(mut self, program: impl Into<String>, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.hooks
+            .push(WatchHook::Command(program.into(), args.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    /// Re-resolve every entry once. The first check only records a baseline; subsequent checks notify
+    /// the hooks if any builds, hashes or files changed, and return the changes.
+    pub fn check(&mut self) -> Result<CatalogDiff, RidoError> {
+        let mut current = Catalog::snapshot_entries(&self.registry, self.entries.iter().cloned(), self.resolve_remote);
+        let Some(last) = self.last.take() else {
+            self.last = Some(current);
+            return Ok(CatalogDiff::default());
+        };
+
+        let diff = last.diff(&current);
+        let changes = CatalogDiff {
+            new_builds: diff.new_builds,
+            changed_hashes: diff.changed_hashes,
+            changed_files: diff.changed_files,
+            ..Default::default()
+        };
+
+        // Keep the last known image for entries which failed to resolve, so a transient failure isn't reported as a change later
+        for failure in &current.failures {
+            if let Some(image) = last.get(&failure.key) {
+                current.images.push(image.clone());
+            }
+        }
+        self.last = Some(current);
+
+        if !changes.is_empty() {
+            self.notify(&changes)?;
+        }
+        Ok(changes)
+    }
+
+    /// Check for changes every interval, forever. Errors are passed to `on_error` rather than stopping the watch.
+    pub fn run(&mut self, mut on_error: impl FnMut(RidoError)) -> ! {
+        loop {
+            if let Err(e) = self.check() {
+                on_error(e);
+            }
+            thread::sleep(self.interval);
+        }
+    }
+
+    fn notify(&mut self, changes: &CatalogDiff) -> Result<(), RidoError> {
+        let json = serde_json::to_string(changes).map_err(RidoError::JSONParsing)?;
+        // Every hook is run even if an earlier one fails; the first error is returned
+        let mut result = Ok(());
+        for hook in &mut self.hooks {
+            let hook_result = match hook {
+                WatchHook::Callback(callback) => {
+                    callback(changes);
+                    Ok(())
+                }
+                WatchHook::Webhook(url) => send_webhook(url, &json),
+                WatchHook::Command(program, args) => run_command(program, args, &json),
+            };
+            if result.is_ok() {
+                result = hook_result;
+            }
+        }
+        result
+    }
+}
+
+fn send_webhook(url: &Url, json: &str) -> Result<(), RidoError> {
+    let client = reqwest::blocking::Client::new();
+    client
+        .post(url.clone())
+        .header(CONTENT_TYPE, "application/json")
+        .body(json.to_string())
+        .send()?
+        .error_for_status()?;
+    Ok(())
+}
+
+fn run_command(program: &str, args: &[String], json: &str) -> Result<(), RidoError> {
+    let mut child = Command::new(program).args(args).stdin(Stdio::piped()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(json.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(RidoError::Hook(format!("{program} exited with {status}")));
+    }
+    Ok(())
+}